@@ -0,0 +1,135 @@
+// pager/src/backend
+
+// A backend is where finished frames go. The terminal is one, a grid
+// of cells in memory is another, which shows exactly what would be on
+// the terminal without needing one.
+
+use crate::screen::Cell;
+use crossterm::{QueueableCommand, cursor, style, terminal};
+use crossterm::style::Colors;
+use std::env;
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthChar;
+
+pub trait Backend {
+    // start a frame of the given size, on a cleared backend if clear
+    fn begin(&mut self, w: u16, h: u16, clear: bool) -> io::Result<()>;
+    // a cell that changed since the last frame
+    fn put(&mut self, x: u16, y: u16, cell: Cell) -> io::Result<()>;
+    // finish the frame, leaving the cursor somewhere or hiding it
+    fn end(&mut self, cursor: Option<(u16, u16)>) -> io::Result<()>;
+}
+// crossterm commands written to a terminal
+#[derive(Debug)]
+pub struct Terminal<W: Write> {
+    out: W,
+    // whether frames are sent as synchronized updates
+    sync: bool,
+    // where the terminal will print next, and in what colors
+    at: Option<(u16, u16)>,
+    colors: Option<Colors>,
+}
+impl<W: Write> Terminal<W> {
+    pub fn new(out: W) -> Self {
+        Self {out, sync: Self::cansync(), at: None, colors: None}
+    }
+    // the sequences set a private mode, which terminals without it
+    // ignore. plain consoles are left out to be safe
+    fn cansync() -> bool {
+        let term = env::var("TERM").unwrap_or_default();
+        !matches!(term.as_str(), "" | "dumb" | "linux" | "vt100" | "vt220")
+    }
+}
+impl<W: Write> Backend for Terminal<W> {
+    fn begin(&mut self, _: u16, _: u16, clear: bool) -> io::Result<()> {
+        if self.sync {
+            self.out.queue(terminal::BeginSynchronizedUpdate)?;
+        }
+        if clear {
+            self.out.queue(terminal::Clear(terminal::ClearType::All))?;
+        }
+        self.at = None;
+        self.colors = None;
+        Ok(())
+    }
+    // the continuation of a wide character was drawn along with it
+    fn put(&mut self, x: u16, y: u16, cell: Cell) -> io::Result<()> {
+        if cell.iscontinuation() {
+            return Ok(())
+        }
+        if self.at != Some((x, y)) {
+            self.out.queue(cursor::MoveTo(x, y))?;
+        }
+        if self.colors != Some(cell.colors) {
+            self.out.queue(style::SetColors(cell.colors))?;
+            self.colors = Some(cell.colors);
+        }
+        self.out.queue(style::Print(cell.ch))?;
+        let width = u16::try_from(cell.ch.width().unwrap_or(1)).unwrap_or(1);
+        self.at = Some((x + width, y));
+        Ok(())
+    }
+    fn end(&mut self, cursor: Option<(u16, u16)>) -> io::Result<()> {
+        self.out.queue(style::ResetColor)?;
+        match cursor {
+            Some((x, y)) => self.out.queue(cursor::MoveTo(x, y))?.queue(cursor::Show)?,
+            None => self.out.queue(cursor::Hide)?,
+        };
+        if self.sync {
+            self.out.queue(terminal::EndSynchronizedUpdate)?;
+        }
+        self.out.flush()
+    }
+}
+// cells in memory, for looking at frames without a terminal
+#[derive(Clone, Debug, Default)]
+pub struct Grid {
+    pub w: u16,
+    pub h: u16,
+    pub cells: Vec<Cell>,
+    pub cursor: Option<(u16, u16)>,
+}
+impl Grid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cell(&self, x: u16, y: u16) -> Cell {
+        self.cells[usize::from(y) * usize::from(self.w) + usize::from(x)]
+    }
+    // the characters on each row, without trailing blanks
+    pub fn rows(&self) -> Vec<String> {
+        self.cells
+            .chunks(std::cmp::max(usize::from(self.w), 1))
+            .map(|row| {
+                let text: String = row
+                    .iter()
+                    .filter(|c| !c.iscontinuation())
+                    .map(|c| c.ch)
+                    .collect();
+                text.trim_end().to_string()
+            })
+            .collect()
+    }
+}
+impl Backend for Grid {
+    fn begin(&mut self, w: u16, h: u16, clear: bool) -> io::Result<()> {
+        if clear || (w, h) != (self.w, self.h) {
+            *self = Self {
+                w,
+                h,
+                cells: vec![Cell::blank(); usize::from(w) * usize::from(h)],
+                cursor: None,
+            };
+        }
+        Ok(())
+    }
+    fn put(&mut self, x: u16, y: u16, cell: Cell) -> io::Result<()> {
+        let i = usize::from(y) * usize::from(self.w) + usize::from(x);
+        self.cells[i] = cell;
+        Ok(())
+    }
+    fn end(&mut self, cursor: Option<(u16, u16)>) -> io::Result<()> {
+        self.cursor = cursor;
+        Ok(())
+    }
+}
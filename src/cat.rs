@@ -0,0 +1,138 @@
+// pager/src/cat
+
+// Documents printed straight to the output, as cat would, for when
+// there is no terminal to page on, or when they fit on one screen.
+
+use crate::config::{ColorMode, Options};
+use crate::doc::Source;
+use crate::tag::{GetColors, Tag};
+use crate::theme::{Depth, Theme};
+use crate::util::{self, Layout};
+use crossterm::{QueueableCommand, style};
+use std::io::{self, Write};
+
+// rows of a line, laid out as the pager would
+fn rows(tag: &Tag, text: &str, w: u16, options: &Options) -> Vec<String> {
+    let text = util::showcontrols(&util::expandtabs(text, options.tabstop));
+    match options.wrap {
+        true => tag.wrap(&text, w),
+        false => tag.cut(&text, w),
+    }
+}
+// whether the documents take no more than h rows, together. only
+// as many lines as fit are looked at
+pub fn fits(sources: &[&dyn Source<Tag>], w: u16, h: usize, options: &Options) -> bool {
+    let mut total = 0;
+    for source in sources.iter() {
+        for i in 0..source.len() {
+            let (tag, text) = source.line(i);
+            total += std::cmp::max(rows(&tag, &text, w, options).len(), 1);
+            if total > h {
+                return false
+            }
+        }
+    }
+    true
+}
+// whether printed rows are colored. NO_COLOR and dumb terminals,
+// which have no colors, are only overridden by --color always
+pub fn colored(mode: ColorMode, tty: bool, depth: Depth) -> bool {
+    match mode {
+        ColorMode::Auto => tty && depth != Depth::None,
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+    }
+}
+// print a document laid out for a width, or for no width at all when
+// there is none. with a theme, rows are in the foreground colors of
+// their tags
+pub fn print(
+    out: &mut impl Write,
+    source: &dyn Source<Tag>,
+    w: Option<u16>,
+    options: &Options,
+    theme: Option<&Theme>,
+) -> io::Result<()> {
+    for i in 0..source.len() {
+        let (tag, text) = source.line(i);
+        let rows = match w {
+            Some(w) => rows(&tag, &text, w, options),
+            None => {
+                let text = util::showcontrols(&util::expandtabs(&text, options.tabstop));
+                tag.cut(&text, u16::MAX)
+            }
+        };
+        for row in rows.iter() {
+            let fg = theme.and_then(|t| tag.getcolors(t).foreground);
+            if !row.is_empty() && let Some(fg) = fg {
+                out.queue(style::SetForegroundColor(fg))?
+                    .queue(style::Print(row))?
+                    .queue(style::ResetColor)?;
+            } else {
+                out.write_all(row.as_bytes())?;
+            }
+            out.write_all(b"\n")?;
+        }
+    }
+    out.flush()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc() -> Vec<(Tag, String)> {
+        vec![
+            (Tag::Heading, String::from("Title")),
+            (Tag::Text, String::new()),
+            (Tag::Text, String::from("some words to wrap")),
+        ]
+    }
+    fn printed(w: Option<u16>, options: &Options, theme: Option<&Theme>) -> String {
+        let mut out = vec![];
+        print(&mut out, &doc(), w, options, theme).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+    // the text of SGR escapes removed
+    fn plain(text: &str) -> String {
+        let mut plain = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("\x1b[") {
+            plain.push_str(&rest[..start]);
+            let end = rest[start..].find('m').unwrap();
+            rest = &rest[start + end + 1..];
+        }
+        plain + rest
+    }
+    #[test]
+    fn fitting() {
+        let doc = doc();
+        let options = Options::default();
+        // the last line wraps onto a second row
+        assert!(fits(&[&doc], 10, 4, &options));
+        assert!(!fits(&[&doc], 10, 3, &options));
+        assert!(fits(&[&doc], 20, 3, &options));
+        assert!(!fits(&[&doc, &doc], 20, 5, &options));
+        let cut = Options {wrap: false, ..Options::default()};
+        assert!(fits(&[&doc], 10, 3, &cut));
+    }
+    #[test]
+    fn printing() {
+        let options = Options::default();
+        assert_eq!(printed(None, &options, None), "Title\n\nsome words to wrap\n");
+        assert_eq!(printed(Some(10), &options, None), "Title\n\nsome words\nto wrap\n");
+        let theme = Theme::default();
+        let colored = printed(None, &options, Some(&theme));
+        assert!(colored.starts_with("\x1b[38;"), "{:?}", colored);
+        assert_ne!(colored, plain(&colored));
+        assert_eq!(plain(&colored), "Title\n\nsome words to wrap\n");
+    }
+    #[test]
+    fn coloring() {
+        assert!(colored(ColorMode::Auto, true, Depth::Ansi16));
+        assert!(!colored(ColorMode::Auto, false, Depth::TrueColor));
+        // as with NO_COLOR set
+        assert!(!colored(ColorMode::Auto, true, Depth::None));
+        assert!(colored(ColorMode::Always, false, Depth::None));
+        assert!(!colored(ColorMode::Never, true, Depth::TrueColor));
+    }
+}
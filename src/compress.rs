@@ -0,0 +1,64 @@
+// pager/src/compress
+
+// Compressed files are recognized by their magic bytes and read
+// through a streaming decoder, so they open like the plain file.
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use lzma_rust2::XzReader;
+use ruzstd::decoding::StreamingDecoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Compression {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+impl Compression {
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1f, 0x8b, ..] => Some(Self::Gzip),
+            [b'B', b'Z', b'h', ..] => Some(Self::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Self::Xz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+    // extension the compressor adds to the file name
+    pub fn fromext(ext: &str) -> Option<Self> {
+        match ext {
+            "gz" => Some(Self::Gzip),
+            "bz2" => Some(Self::Bzip2),
+            "xz" => Some(Self::Xz),
+            "zst" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Bzip2 => "bzip2",
+            Self::Xz => "xz",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+// open a file for reading, decompressing it as it is read
+pub fn open(path: &str) -> io::Result<(Box<dyn Read>, Option<Compression>)> {
+    let mut file = BufReader::new(File::open(path)?);
+    let compression = Compression::detect(file.fill_buf()?);
+    let reader: Box<dyn Read> = match compression {
+        None => Box::new(file),
+        Some(Compression::Gzip) => Box::new(MultiGzDecoder::new(file)),
+        Some(Compression::Bzip2) => Box::new(MultiBzDecoder::new(file)),
+        Some(Compression::Xz) => Box::new(XzReader::new(file, true)),
+        Some(Compression::Zstd) => Box::new(
+            StreamingDecoder::new(file)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        ),
+    };
+    Ok((reader, compression))
+}
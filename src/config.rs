@@ -0,0 +1,428 @@
+// pager/src/config
+
+// Settings that apply to every document. They come from a config
+// file, then the command line, and are otherwise left at their
+// defaults. The config file is $XDG_CONFIG_HOME/pager/config, or
+// ~/.config/pager/config, and has a setting on each line:
+//
+//     # comment
+//     theme light
+//     color heading 225,105,105 #000000
+//     depth 256
+//     banner {index}/{count}: {path}{compression}
+//     wrap off
+//     numbers relative
+//     cursor on
+//     tabstop 4
+//     ignorecase smart
+//     wrapscan on
+//     keys less
+//     bind g g down
+//     unbind q
+
+use crate::format::{DocumentFormat, Registry};
+use crate::keys::{Bindings, Command, Key, PRESETS};
+use crate::theme::{self, Depth, Theme, THEMES};
+use crossterm::style::Colors;
+use encoding_rs::Encoding;
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+// how the banner is shown unless the config file says otherwise
+pub const BANNER: &str = "{index}/{count}: {path}{compression}";
+
+// whether searches tell upper and lower case apart
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Case {
+    Sensitive,
+    Insensitive,
+    // insensitive, unless the pattern has upper case letters
+    #[default]
+    Smart,
+}
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchOptions {
+    pub case: Case,
+    // whether a search carries on from the top when it reaches the end
+    pub wrapscan: bool,
+}
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {case: Case::Smart, wrapscan: true}
+    }
+}
+impl SearchOptions {
+    pub fn matches(&self, text: &str, pattern: &str) -> bool {
+        let ignore = match self.case {
+            Case::Sensitive => false,
+            Case::Insensitive => true,
+            Case::Smart => !pattern.chars().any(char::is_uppercase),
+        };
+        match ignore {
+            true => text.to_lowercase().contains(&pattern.to_lowercase()),
+            false => text.contains(pattern),
+        }
+    }
+}
+
+// whether printed documents are colored
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ColorMode {
+    // when printing to a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+impl ColorMode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+}
+// how lines are numbered in the gutter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Numbers {
+    #[default]
+    Off,
+    Absolute,
+    // counting from the cursor, which shows its own number
+    Relative,
+}
+impl Numbers {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Self::Off),
+            "absolute" => Some(Self::Absolute),
+            "relative" => Some(Self::Relative),
+            _ => None,
+        }
+    }
+    // off, absolute, relative, then off again
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Off => Self::Absolute,
+            Self::Absolute => Self::Relative,
+            Self::Relative => Self::Off,
+        }
+    }
+}
+// where a document is opened
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Start {
+    #[default]
+    Top,
+    // a line, counting from 1
+    Line(usize),
+    // the first line containing the text
+    Search(String),
+}
+impl Start {
+    // "+42" or "+/pattern", as less and vi take them
+    pub fn parse(arg: &str) -> Option<Self> {
+        let arg = arg.strip_prefix('+')?;
+        if let Some(pattern) = arg.strip_prefix('/') {
+            return Some(Self::Search(pattern.to_string()))
+        }
+        match arg.is_empty() {
+            // a bare '+' goes to the end
+            true => Some(Self::Line(usize::MAX)),
+            false => arg.parse().ok().map(Self::Line),
+        }
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Options {
+    // format of every file, chosen for each file if None
+    pub format: Option<Rc<dyn DocumentFormat>>,
+    // encoding of every file, guessed if None
+    pub encoding: Option<&'static Encoding>,
+    pub wrap: bool,
+    pub numbers: Numbers,
+    // whether the terminal cursor is shown on the selected line, as
+    // well as its highlight
+    pub cursor: bool,
+    pub start: Start,
+    // print instead of paging when everything fits on one screen
+    pub quitifonescreen: bool,
+    pub color: ColorMode,
+    // theme, and changes made to its colors in order
+    pub theme: String,
+    pub colors: Vec<(String, Colors)>,
+    // colors the terminal shows, detected if None
+    pub depth: Option<Depth>,
+    // the banner, with {index}, {count}, {path}, {format} and
+    // {compression} filled in
+    pub banner: String,
+    // columns between tab stops
+    pub tabstop: usize,
+    pub search: SearchOptions,
+    pub config: Option<PathBuf>,
+    // preset of key bindings, and changes made to it in order.
+    // a sequence without a command is unbound
+    pub keys: String,
+    pub binds: Vec<(Vec<Key>, Option<Command>)>,
+}
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            format: None,
+            encoding: None,
+            wrap: true,
+            numbers: Numbers::Off,
+            cursor: false,
+            start: Start::Top,
+            quitifonescreen: false,
+            color: ColorMode::Auto,
+            theme: String::from(THEMES[0]),
+            colors: vec![],
+            depth: None,
+            banner: String::from(BANNER),
+            tabstop: 8,
+            search: SearchOptions::default(),
+            config: None,
+            keys: String::from(PRESETS[0]),
+            binds: vec![],
+        }
+    }
+}
+impl Options {
+    // the format given, or the one the registry picks for the file
+    pub fn formatfor(&self, formats: &Registry, path: &str) -> Rc<dyn DocumentFormat> {
+        match &self.format {
+            Some(f) => f.clone(),
+            None => formats.detect(path),
+        }
+    }
+    // the theme with the changes made to it, in colors the
+    // terminal can show
+    pub fn palette(&self) -> Theme {
+        let mut theme = Theme::byname(&self.theme).unwrap_or_default();
+        for (target, colors) in self.colors.iter() {
+            theme.set(target, *colors);
+        }
+        theme.fordepth(self.depth.unwrap_or_else(Depth::detect))
+    }
+    // the preset with the changes made to it
+    pub fn bindings(&self) -> Bindings {
+        let mut bindings = Bindings::preset(&self.keys).unwrap_or_default();
+        for (keys, command) in self.binds.iter() {
+            match command {
+                Some(c) => bindings.bind(keys.clone(), *c),
+                None => bindings.unbind(keys),
+            }
+        }
+        bindings
+    }
+    // the config file in the XDG config directory, if there is one
+    pub fn configpath() -> Option<PathBuf> {
+        let dir = match env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+            Some(d) => PathBuf::from(d),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(dir.join("pager").join("config")).filter(|p| p.is_file())
+    }
+    // settings that can't be read are left as they were. what was
+    // wrong with them is returned, naming the file and line
+    pub fn load(&mut self, path: &Path) -> Vec<String> {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return vec![format!("{}: {}", path.display(), e)],
+        };
+        let mut errors = vec![];
+        for (i, line) in text.lines().enumerate() {
+            if let Err(e) = self.setting(line) {
+                errors.push(format!("{}:{}: {}", path.display(), i + 1, e));
+            }
+        }
+        errors
+    }
+    fn setting(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        let Some((name, value)) = line
+            .split_once(char::is_whitespace)
+            .or(Some((line, "")))
+            .filter(|(n, _)| !n.is_empty() && !n.starts_with('#'))
+        else {
+            return Ok(())
+        };
+        let value = value.trim();
+        let words: Vec<&str> = value.split_whitespace().collect();
+        match (name, words.as_slice()) {
+            ("theme", [name]) => match THEMES.contains(name) {
+                true => self.theme = name.to_string(),
+                false => return Err(format!(
+                    "unknown theme: {}, try {}", name, THEMES.join(", "))),
+            },
+            ("color", [target, colors @ ..]) if (1..=2).contains(&colors.len()) => {
+                if !theme::TARGETS.contains(target) {
+                    return Err(format!(
+                        "unknown color: {}, try {}", target, theme::TARGETS.join(", ")))
+                }
+                let mut parsed = vec![];
+                for c in colors.iter() {
+                    let Some(color) = theme::parsecolor(c) else {
+                        return Err(format!("not a color: {}", c))
+                    };
+                    parsed.push(color);
+                }
+                let colors = Colors {
+                    foreground: parsed.first().copied(),
+                    background: parsed.get(1).copied(),
+                };
+                self.colors.push((target.to_string(), colors));
+            }
+            ("depth", [value]) => self.depth = match *value {
+                "auto" => None,
+                "none" => Some(Depth::None),
+                "16" => Some(Depth::Ansi16),
+                "256" => Some(Depth::Ansi256),
+                "truecolor" => Some(Depth::TrueColor),
+                _ => return Err(format!(
+                    "expected auto, none, 16, 256 or truecolor, not {}", value)),
+            },
+            // the rest of the line, spaces and all
+            ("banner", [_, ..]) => self.banner = value.to_string(),
+            ("wrap", [value]) => self.wrap = onoff(value)?,
+            ("cursor", [value]) => self.cursor = onoff(value)?,
+            ("numbers", [value]) => match Numbers::parse(value) {
+                Some(n) => self.numbers = n,
+                None => return Err(format!("expected off, absolute or relative, not {}", value)),
+            },
+            ("tabstop", [value]) => match value.parse() {
+                Ok(n) if n > 0 => self.tabstop = n,
+                _ => return Err(format!("tabstop should be a number above 0, not {}", value)),
+            },
+            ("ignorecase", [value]) => self.search.case = match *value {
+                "on" => Case::Insensitive,
+                "off" => Case::Sensitive,
+                "smart" => Case::Smart,
+                _ => return Err(format!("expected on, off or smart, not {}", value)),
+            },
+            ("wrapscan", [value]) => self.search.wrapscan = onoff(value)?,
+            ("keys", [preset]) => match PRESETS.contains(preset) {
+                true => self.keys = preset.to_string(),
+                false => return Err(format!(
+                    "unknown keys: {}, try {}", preset, PRESETS.join(", "))),
+            },
+            ("bind", [keys @ .., command]) if !keys.is_empty() => {
+                let Some(command) = Command::byname(command) else {
+                    return Err(format!("unknown command: {}", command))
+                };
+                self.binds.push((parsekeys(keys)?, Some(command)));
+            }
+            ("unbind", keys) if !keys.is_empty() => {
+                self.binds.push((parsekeys(keys)?, None));
+            }
+            (
+                "theme" | "color" | "depth" | "banner" | "wrap" | "numbers"
+                | "cursor" | "tabstop" | "ignorecase" | "wrapscan" | "keys"
+                | "bind" | "unbind",
+                _,
+            ) => {
+                return Err(format!("wrong number of values for {}", name))
+            }
+            _ => return Err(format!("unknown setting: {}", name)),
+        }
+        Ok(())
+    }
+}
+fn onoff(value: &str) -> Result<bool, String> {
+    match value {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("expected on or off, not {}", value)),
+    }
+}
+fn parsekeys(names: &[&str]) -> Result<Vec<Key>, String> {
+    names
+        .iter()
+        .map(|n| Key::parse(n).ok_or_else(|| format!("unknown key: {}", n)))
+        .collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    // options loaded from a config file of these lines, and what
+    // was wrong with it
+    fn load(name: &str, text: &str) -> (Options, Vec<String>, String) {
+        let dir = TempDir::new("config");
+        let path = dir.file(name, text.as_bytes());
+        let mut options = Options::default();
+        let errors = options.load(Path::new(&path));
+        (options, errors, path)
+    }
+    #[test]
+    fn settings() {
+        let text = "# comment\n\n  theme light\ndepth 256\nbanner {path} - {format}\n\
+            wrap off\nnumbers relative\ncursor on\ntabstop 4\nignorecase off\n\
+            wrapscan off\nkeys vi\ncolor heading 225,105,105 #000000\n";
+        let (options, errors, _) = load("settings", text);
+        assert_eq!(errors, Vec::<String>::new());
+        assert_eq!(options.theme, "light");
+        assert_eq!(options.depth, Some(Depth::Ansi256));
+        assert_eq!(options.banner, "{path} - {format}");
+        assert!(!options.wrap);
+        assert_eq!(options.numbers, Numbers::Relative);
+        assert!(options.cursor);
+        assert_eq!(options.tabstop, 4);
+        assert_eq!(options.search.case, Case::Sensitive);
+        assert!(!options.search.wrapscan);
+        assert_eq!(options.keys, "vi");
+        assert_eq!(options.colors.len(), 1);
+    }
+    // each error names the file and line, and leaves the default
+    #[test]
+    fn errors_name_the_line() {
+        let text = "wrap maybe\ntabstop 0\n# fine\ndepth 17\nfrobnicate on\ncursor\nbind g nowhere\n";
+        let (options, errors, path) = load("errors", text);
+        assert_eq!(errors, [
+            format!("{}:1: expected on or off, not maybe", path),
+            format!("{}:2: tabstop should be a number above 0, not 0", path),
+            format!("{}:4: expected auto, none, 16, 256 or truecolor, not 17", path),
+            format!("{}:5: unknown setting: frobnicate", path),
+            format!("{}:6: wrong number of values for cursor", path),
+            format!("{}:7: unknown command: nowhere", path),
+        ]);
+        let defaults = Options::default();
+        assert_eq!(options.wrap, defaults.wrap);
+        assert_eq!(options.tabstop, defaults.tabstop);
+        assert_eq!(options.depth, defaults.depth);
+        assert_eq!(options.cursor, defaults.cursor);
+        assert!(options.binds.is_empty());
+    }
+    #[test]
+    fn missing_file() {
+        let mut options = Options::default();
+        let errors = options.load(Path::new("/nonexistent/pager/config"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("/nonexistent/pager/config: "), "{:?}", errors);
+    }
+    // binds change the preset in the order they are given
+    #[test]
+    fn bindings() {
+        let (options, errors, _) = load("bindings", "keys vi\nbind g g down\nunbind j\nbind Q quit\n");
+        assert_eq!(errors, Vec::<String>::new());
+        let mut bindings = options.bindings();
+        let key = |name| Key::parse(name).unwrap();
+        assert_eq!(bindings.feed(key("g")), None);
+        assert_eq!(bindings.feed(key("g")), Some(Command::Down));
+        assert_eq!(bindings.feed(key("j")), None);
+        assert_eq!(bindings.feed(key("Q")), Some(Command::Quit));
+    }
+    #[test]
+    fn start() {
+        assert_eq!(Start::parse("+42"), Some(Start::Line(42)));
+        assert_eq!(Start::parse("+/fn main"), Some(Start::Search(String::from("fn main"))));
+        assert_eq!(Start::parse("+"), Some(Start::Line(usize::MAX)));
+        assert_eq!(Start::parse("+x"), None);
+        assert_eq!(Start::parse("42"), None);
+    }
+}
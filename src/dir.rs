@@ -0,0 +1,120 @@
+// pager/src/dir
+
+// Directories are shown as generated documents, with a link to each
+// entry, so that following links browses the file system.
+
+use crate::tag::Tag;
+use crate::util;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// names longer than this push the size and time out of line
+const NAMEWIDTH: usize = 40;
+
+// heading, link to the parent, then a link to each entry,
+// directories first
+pub fn listing(path: &str) -> io::Result<Vec<(Tag, String)>> {
+    let dir = Path::new(path);
+    let mut entries: Vec<(String, Metadata)> = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        // follow symlinks, unless they are broken
+        let meta = match fs::metadata(entry.path()) {
+            Ok(m) => m,
+            Err(_) => entry.metadata()?,
+        };
+        entries.push((entry.file_name().to_string_lossy().into_owned(), meta));
+    }
+    entries.sort_by(|(a, am), (b, bm)| {
+        bm.is_dir()
+            .cmp(&am.is_dir())
+            .then_with(|| a.to_lowercase().cmp(&b.to_lowercase()))
+    });
+    let heading = match fs::canonicalize(dir) {
+        Ok(p) => p.to_string_lossy().into_owned(),
+        Err(_) => path.to_string(),
+    };
+    let width = entries
+        .iter()
+        .map(|(name, _)| util::width(name) + 1)
+        .max()
+        .unwrap_or(0)
+        .clamp(2, NAMEWIDTH);
+    let parent = dir.join("..").to_string_lossy().into_owned();
+    let mut vec = vec![
+        (Tag::Heading, heading),
+        (Tag::Link(parent), String::from("../")),
+    ];
+    for (name, meta) in entries.iter() {
+        let target = dir.join(name).to_string_lossy().into_owned();
+        let (name, size) = match meta.is_dir() {
+            true => (format!("{}/", name), String::from("-")),
+            false => (name.clone(), util::humansize(meta.len())),
+        };
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| datetime(d.as_secs()))
+            .unwrap_or_default();
+        let text = format!("{} {:>6}  {}", util::pad(&name, width), size, mtime);
+        vec.push((Tag::Link(target), text));
+    }
+    Ok(vec)
+}
+// "YYYY-MM-DD HH:MM" in UTC, from seconds since the epoch
+fn datetime(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let (hour, minute) = ((secs % 86400) / 3600, (secs % 3600) / 60);
+    // civil from days, after Howard Hinnant
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = match mp < 10 {
+        true => mp + 3,
+        false => mp - 9,
+    };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, hour, minute)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    // directories come first, then names whatever their case
+    #[test]
+    fn order() {
+        let dir = TempDir::new("dir");
+        dir.file("b.txt", b"12345");
+        dir.file("A.txt", b"");
+        dir.file("c", b"");
+        fs::create_dir(dir.path.join("zdir")).unwrap();
+        fs::create_dir(dir.path.join("Bdir")).unwrap();
+        let path = dir.path.to_string_lossy().into_owned();
+        let doc = listing(&path).unwrap();
+        let names: Vec<&str> = doc[2..]
+            .iter()
+            .map(|(_, text)| text.split(' ').next().unwrap())
+            .collect();
+        assert_eq!(names, ["Bdir/", "zdir/", "A.txt", "b.txt", "c"]);
+        assert_eq!(doc[0].0, Tag::Heading);
+        assert_eq!(doc[1], (Tag::Link(format!("{}/..", path)), String::from("../")));
+        assert_eq!(doc[5].0, Tag::Link(format!("{}/b.txt", path)));
+        assert!(doc[5].1.starts_with("b.txt       5  "), "{:?}", doc[5].1);
+        assert!(doc[2].1.starts_with("Bdir/       -  "), "{:?}", doc[2].1);
+    }
+    #[test]
+    fn dates() {
+        assert_eq!(datetime(0), "1970-01-01 00:00");
+        assert_eq!(datetime(951782400 + 86399), "2000-02-29 23:59");
+        assert_eq!(datetime(1709210096), "2024-02-29 12:34");
+        assert_eq!(datetime(4102444800), "2100-01-01 00:00");
+    }
+}
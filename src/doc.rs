@@ -0,0 +1,439 @@
+// pager/src/doc
+
+// Documents are read a line at a time through a Source. Small files
+// are parsed whole into a list of lines. Large files are read from disk
+// a block at a time and only the lines in view are ever decoded, so
+// memory use doesn't grow with the size of the file. Open documents are
+// kept in a cache, so tabs on the same file share one source.
+
+use crate::compress::{self, Compression};
+use crate::dir;
+use crate::encoding;
+use crate::format::DocumentFormat;
+use crate::tag::Tag;
+use encoding_rs::Encoding;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// files this large are read lazily where the format allows it
+pub const LARGE: usize = 16 << 20;
+// the start of every STRIDE'th line is kept
+const STRIDE: usize = 64;
+// bytes read from a large file at once, and how many of them are kept
+const BLOCK: usize = 64 << 10;
+const BLOCKS: usize = 16;
+
+// lines of a document, by index
+pub trait Source<T>: Debug {
+    fn len(&self) -> usize;
+    fn line(&self, i: usize) -> (T, String);
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+impl<T: Clone + Debug> Source<T> for Vec<(T, String)> {
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+    fn line(&self, i: usize) -> (T, String) {
+        self[i].clone()
+    }
+}
+// the bytes of a file, in memory or read from disk as they are needed
+#[derive(Debug)]
+pub enum Bytes {
+    Paged(Paged),
+    Owned(Vec<u8>),
+}
+impl Bytes {
+    // small files are read whole. compressed files are decompressed
+    // into memory, or into an unlinked temporary file when they turn
+    // out to be large
+    pub fn open(path: &str) -> io::Result<(Self, Option<Compression>)> {
+        let (reader, compression) = compress::open(path)?;
+        let bytes = match compression {
+            Some(_) => Self::decompress(reader)?,
+            None => Self::read(File::open(path)?)?,
+        };
+        Ok((bytes, compression))
+    }
+    fn read(mut file: File) -> io::Result<Self> {
+        if file.metadata()?.len() >= LARGE as u64 {
+            return Ok(Self::Paged(Paged::new(file)?))
+        }
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)?;
+        Ok(Self::Owned(bytes))
+    }
+    fn decompress(reader: Box<dyn Read>) -> io::Result<Self> {
+        let mut bytes = vec![];
+        let mut reader = reader.take(LARGE as u64);
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < LARGE {
+            return Ok(Self::Owned(bytes))
+        }
+        let mut file = tempfile()?;
+        io::Write::write_all(&mut file, &bytes)?;
+        drop(bytes);
+        io::copy(&mut reader.into_inner(), &mut file)?;
+        Self::read(file)
+    }
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Paged(p) => p.len,
+            Self::Owned(v) => v.len(),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // the bytes from start to end, fewer if the file was cut short
+    pub fn get(&self, start: usize, end: usize) -> Cow<'_, [u8]> {
+        let p = match self {
+            Self::Paged(p) => p,
+            Self::Owned(v) => {
+                let end = std::cmp::min(end, v.len());
+                return Cow::Borrowed(v.get(start..end).unwrap_or_default())
+            }
+        };
+        let mut bytes = vec![];
+        let mut at = start;
+        while at < end {
+            let block = p.block(at / BLOCK);
+            let from = at % BLOCK;
+            if from >= block.len() {
+                break
+            }
+            let to = std::cmp::min(block.len(), from + end - at);
+            bytes.extend_from_slice(&block[from..to]);
+            at += to - from;
+        }
+        Cow::Owned(bytes)
+    }
+    pub fn whole(&self) -> Cow<'_, [u8]> {
+        self.get(0, self.len())
+    }
+    // where a byte is next found from a position on
+    pub fn find(&self, byte: u8, from: usize) -> Option<usize> {
+        let p = match self {
+            Self::Paged(p) => p,
+            Self::Owned(v) => return memchr::memchr(byte, v.get(from..)?).map(|i| from + i),
+        };
+        let mut at = from;
+        loop {
+            let block = p.block(at / BLOCK);
+            let rest = block.get(at % BLOCK..).filter(|r| !r.is_empty())?;
+            if let Some(i) = memchr::memchr(byte, rest) {
+                return Some(at + i)
+            }
+            if block.len() < BLOCK {
+                return None
+            }
+            at += rest.len();
+        }
+    }
+    // go through the bytes once from the start, a piece at a time,
+    // without keeping them
+    fn scan(&self, mut f: impl FnMut(usize, &[u8])) {
+        let p = match self {
+            Self::Paged(p) => p,
+            Self::Owned(v) => return f(0, v),
+        };
+        let mut at = 0;
+        while let Ok(piece) = p.readat(at, BLOCK * BLOCKS)
+            && !piece.is_empty()
+        {
+            f(at, &piece);
+            at += piece.len();
+        }
+    }
+}
+// a file read a block at a time as it is needed, the blocks read last
+// are kept. the file isn't mapped, so if it is cut short while it is
+// shown, the lines past its new end come back empty rather than
+// taking the pager down
+pub struct Paged {
+    file: File,
+    // the size when it was opened
+    len: usize,
+    // block number and bytes, the one used last at the end
+    blocks: RefCell<Vec<(usize, Rc<Vec<u8>>)>>,
+}
+impl Debug for Paged {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Paged")
+            .field("file", &self.file)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+impl Paged {
+    fn new(file: File) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len()).unwrap_or(usize::MAX);
+        Ok(Self {file, len, blocks: RefCell::new(vec![])})
+    }
+    // up to n bytes from a position on
+    fn readat(&self, at: usize, n: usize) -> io::Result<Vec<u8>> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(at as u64))?;
+        let mut bytes = vec![];
+        file.take(n as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+    fn block(&self, n: usize) -> Rc<Vec<u8>> {
+        let mut blocks = self.blocks.borrow_mut();
+        match blocks.iter().position(|(b, _)| *b == n) {
+            Some(i) => {
+                let block = blocks.remove(i);
+                blocks.push(block);
+            }
+            None => {
+                // a read that fails is taken as the end of the file
+                let bytes = self.readat(n * BLOCK, BLOCK).unwrap_or_default();
+                if blocks.len() >= BLOCKS {
+                    blocks.remove(0);
+                }
+                blocks.push((n, Rc::new(bytes)));
+            }
+        }
+        blocks[blocks.len() - 1].1.clone()
+    }
+}
+// a file nobody else can open, that goes away once it is closed
+fn tempfile() -> io::Result<File> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let name = format!(
+        "pager-{}-{}",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed));
+    let path = std::env::temp_dir().join(name);
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    fs::remove_file(&path)?;
+    Ok(file)
+}
+// lines of a text file in an encoding that keeps '\n' a single byte,
+// each decoded and parsed on its own when asked for
+pub struct Lines {
+    bytes: Bytes,
+    // byte offset of every STRIDE'th line
+    offsets: Vec<usize>,
+    len: usize,
+    encoding: &'static Encoding,
+    parse: fn(&str) -> (Tag, String),
+}
+impl Debug for Lines {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Lines")
+            .field("len", &self.len)
+            .field("encoding", &self.encoding)
+            .finish()
+    }
+}
+impl Lines {
+    pub fn new(
+        bytes: Bytes,
+        encoding: &'static Encoding,
+        parse: fn(&str) -> (Tag, String),
+    ) -> Self {
+        let start = Encoding::for_bom(&bytes.get(0, 3)).map(|(_, n)| n).unwrap_or(0);
+        let mut offsets = vec![start];
+        let mut len = 0;
+        bytes.scan(|at, piece| {
+            let skip = start.saturating_sub(at);
+            for i in memchr::memchr_iter(b'\n', piece.get(skip..).unwrap_or_default()) {
+                len += 1;
+                if len % STRIDE == 0 {
+                    offsets.push(at + skip + i + 1);
+                }
+            }
+        });
+        // the last line needn't end in a newline
+        let end = bytes.len();
+        if end > start && *bytes.get(end - 1, end) != *b"\n" {
+            len += 1;
+        }
+        Self {bytes, offsets, len, encoding, parse}
+    }
+}
+impl Source<Tag> for Lines {
+    fn len(&self) -> usize {
+        self.len
+    }
+    // offsets go stale if the file changes while it is shown. the
+    // lines are still read, just not the ones they were
+    fn line(&self, i: usize) -> (Tag, String) {
+        let mut start = self.offsets[i / STRIDE];
+        for _ in 0..i % STRIDE {
+            start = self.bytes.find(b'\n', start).map_or(self.bytes.len(), |n| n + 1);
+        }
+        let end = self.bytes.find(b'\n', start).unwrap_or(self.bytes.len());
+        let bytes = self.bytes.get(start, end);
+        let line = bytes.strip_suffix(b"\r").unwrap_or(&bytes);
+        let (text, _) = self.encoding.decode_without_bom_handling(line);
+        (self.parse)(&text)
+    }
+}
+// a hex dump of a file, made a line at a time
+#[derive(Debug)]
+pub struct HexDump {
+    bytes: Bytes,
+}
+impl HexDump {
+    pub fn new(bytes: Bytes) -> Self {
+        Self {bytes}
+    }
+}
+impl Source<Tag> for HexDump {
+    fn len(&self) -> usize {
+        self.bytes.len().div_ceil(encoding::HEXWIDTH)
+    }
+    fn line(&self, i: usize) -> (Tag, String) {
+        let start = i * encoding::HEXWIDTH;
+        let end = std::cmp::min(start + encoding::HEXWIDTH, self.bytes.len());
+        (Tag::Pre, encoding::hexline(start, &self.bytes.get(start, end)))
+    }
+}
+// canonical path and format name
+pub type Key = (PathBuf, &'static str);
+// an open document, as tabs see it
+#[derive(Clone, Debug)]
+pub struct Document {
+    pub source: Rc<dyn Source<Tag>>,
+    // how the file was compressed, and its size on disk
+    pub compressed: Option<(Compression, u64)>,
+}
+// a document as the cache keeps it
+#[derive(Clone, Debug)]
+struct Entry {
+    source: Weak<dyn Source<Tag>>,
+    compressed: Option<(Compression, u64)>,
+}
+// documents by canonical path and format. only weak references are
+// kept, a document goes away with the last tab on it
+#[derive(Clone, Debug)]
+pub struct Cache {
+    docs: HashMap<Key, Entry>,
+    // encoding of the documents, guessed if None
+    encoding: Option<&'static Encoding>,
+}
+impl Cache {
+    pub fn new(encoding: Option<&'static Encoding>) -> Self {
+        Self {docs: HashMap::new(), encoding}
+    }
+    // the document from the cache if a tab still has it, else read
+    pub fn open(&mut self, path: &str, format: &Rc<dyn DocumentFormat>) -> io::Result<Document> {
+        let key = Self::key(path, format);
+        if let Some(entry) = self.docs.get(&key)
+            && let Some(source) = entry.source.upgrade()
+        {
+            return Ok(Document {source, compressed: entry.compressed})
+        }
+        self.reload(path, format)
+    }
+    // read the document again, whether it is cached or not
+    pub fn reload(&mut self, path: &str, format: &Rc<dyn DocumentFormat>) -> io::Result<Document> {
+        let doc = self.read(path, format)?;
+        self.docs.retain(|_, e| e.source.strong_count() > 0);
+        let entry = Entry {
+            source: Rc::downgrade(&doc.source),
+            compressed: doc.compressed,
+        };
+        self.docs.insert(Self::key(path, format), entry);
+        Ok(doc)
+    }
+    // directories are listed instead of parsed
+    fn read(&self, path: &str, format: &Rc<dyn DocumentFormat>) -> io::Result<Document> {
+        if Path::new(path).is_dir() {
+            let source = Rc::new(dir::listing(path)?);
+            return Ok(Document {source, compressed: None})
+        }
+        let (bytes, compression) = Bytes::open(path)?;
+        let size = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        Ok(Document {
+            source: format.source(bytes, self.encoding),
+            compressed: compression.map(|c| (c, size)),
+        })
+    }
+    // tabs with the same key show the same document
+    pub fn key(path: &str, format: &Rc<dyn DocumentFormat>) -> Key {
+        let name = format.names().first().copied().unwrap_or_default();
+        (Self::canonical(path), name)
+    }
+    fn canonical(path: &str) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{Gemtext, Registry};
+    use crate::util::TempDir;
+
+    #[test]
+    fn lines_are_read_in_blocks() {
+        let text: String = (0..20000).map(|i| format!("line {}\n", i)).collect();
+        let dir = TempDir::new("doc");
+        let path = dir.file("blocks", text.as_bytes());
+        let bytes = Bytes::Paged(Paged::new(File::open(&path).unwrap()).unwrap());
+        let lines = Lines::new(bytes, encoding_rs::UTF_8, |l| (Tag::Text, l.to_string()));
+        assert_eq!(lines.len(), 20000);
+        assert_eq!(lines.line(0).1, "line 0");
+        assert_eq!(lines.line(12345).1, "line 12345");
+        assert_eq!(lines.line(19999).1, "line 19999");
+    }
+    // nothing is mapped, so a file cut short only loses the lines
+    // that haven't been read yet
+    #[test]
+    fn truncated_files_come_back_short() {
+        let text: String = (0..20000).map(|i| format!("line {}\n", i)).collect();
+        let dir = TempDir::new("doc");
+        let path = dir.file("truncated", text.as_bytes());
+        let bytes = Bytes::Paged(Paged::new(File::open(&path).unwrap()).unwrap());
+        let lines = Lines::new(bytes, encoding_rs::UTF_8, |l| (Tag::Text, l.to_string()));
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(10).unwrap();
+        assert_eq!(lines.line(0).1, "line 0");
+        assert_eq!(lines.line(10000).1, "");
+    }
+    // a file opened again while a tab has it is the same document,
+    // until it is reloaded
+    #[test]
+    fn cache_shares_documents() {
+        let dir = TempDir::new("doc");
+        let path = dir.file("shared.txt", b"one\ntwo\n");
+        let format = Registry::builtin().detect(&path);
+        let mut cache = Cache::new(None);
+        let first = cache.open(&path, &format).unwrap();
+        let second = cache.open(&path, &format).unwrap();
+        assert!(Rc::ptr_eq(&first.source, &second.source));
+        let reloaded = cache.reload(&path, &format).unwrap();
+        assert!(!Rc::ptr_eq(&first.source, &reloaded.source));
+        let third = cache.open(&path, &format).unwrap();
+        assert!(Rc::ptr_eq(&reloaded.source, &third.source));
+    }
+    // large files of every text format are read a line at a time
+    #[test]
+    fn large_files_are_lazy() {
+        let text = "# heading\n=> gemini://example.org example\n".repeat(LARGE / 32);
+        let dir = TempDir::new("doc");
+        let path = dir.file("large.gmi", text.as_bytes());
+        let format = Registry::builtin().detect(&path);
+        assert_eq!(format.names(), Gemtext.names());
+        let doc = Cache::new(None).open(&path, &format).unwrap();
+        assert!(format!("{:?}", doc.source).starts_with("Lines"));
+        assert_eq!(doc.source.len(), LARGE / 16);
+        assert_eq!(doc.source.line(0), (Tag::Heading, String::from("heading")));
+        let link = Tag::Link(String::from("gemini://example.org"));
+        assert_eq!(doc.source.line(LARGE / 16 - 1), (link, String::from("example")));
+    }
+}
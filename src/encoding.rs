@@ -0,0 +1,129 @@
+// pager/src/encoding
+
+// Files are decoded to UTF-8 before they are parsed. The encoding is
+// taken from a byte order mark, or guessed when the file isn't UTF-8,
+// unless one is given. Binary files are shown as a hex dump instead.
+
+use crate::tag::Tag;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+// how much of a file is looked at to tell whether it is binary
+pub const SNIFFLEN: usize = 8192;
+// bytes on each line of a hex dump
+pub const HEXWIDTH: usize = 16;
+
+// decode with the given encoding, or with the one the bytes
+// seem to be in. undecodable bytes become U+FFFD
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> String {
+    let encoding = encoding.unwrap_or_else(|| detect(bytes));
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+// NUL bytes don't appear in text, unless it is UTF-16
+pub fn isbinary(bytes: &[u8]) -> bool {
+    let start = &bytes[..std::cmp::min(bytes.len(), SNIFFLEN)];
+    Encoding::for_bom(start).is_none() && start.contains(&0)
+}
+// guess from the start of a file too large to look at whole
+pub fn sniff(bytes: &[u8]) -> &'static Encoding {
+    let start = &bytes[..std::cmp::min(bytes.len(), SNIFFLEN)];
+    match std::str::from_utf8(start) {
+        // a character is cut off at the end
+        Err(e) if e.error_len().is_none() => detect(&start[..e.valid_up_to()]),
+        _ => detect(start),
+    }
+}
+pub fn hexdump(bytes: &[u8]) -> Vec<(Tag, String)> {
+    bytes
+        .chunks(HEXWIDTH)
+        .enumerate()
+        .map(|(i, chunk)| (Tag::Pre, hexline(i * HEXWIDTH, chunk)))
+        .collect()
+}
+// offset, bytes in hex, then the printable ones as they are
+pub fn hexline(offset: usize, chunk: &[u8]) -> String {
+    let mut hex = String::new();
+    for j in 0..HEXWIDTH {
+        match chunk.get(j) {
+            Some(b) => hex.push_str(&format!("{:02x} ", b)),
+            None => hex.push_str("   "),
+        }
+        if j == HEXWIDTH / 2 - 1 {
+            hex.push(' ');
+        }
+    }
+    let ascii: String = chunk
+        .iter()
+        .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+            true => char::from(*b),
+            false => '.',
+        })
+        .collect();
+    format!("{:08x}  {} |{}|", offset, hex, ascii)
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{UTF_16LE, WINDOWS_1252};
+
+    #[test]
+    fn detection() {
+        assert_eq!(detect("plain and caf\u{e9}".as_bytes()), UTF_8);
+        assert_eq!(detect(b"\xff\xfeh\0i\0"), UTF_16LE);
+        let latin1 = b"Le caf\xe9 est tr\xe8s bon, merci \xe0 vous. D\xe9j\xe0 vu, \xe9t\xe9 comme hiver.";
+        assert_eq!(detect(latin1), WINDOWS_1252);
+        assert_eq!(decode(latin1, None), "Le caf\u{e9} est tr\u{e8}s bon, merci \u{e0} vous. D\u{e9}j\u{e0} vu, \u{e9}t\u{e9} comme hiver.");
+        // undecodable bytes are replaced when the encoding is given
+        assert_eq!(decode(b"caf\xe9", Some(UTF_8)), "caf\u{fffd}");
+        assert_eq!(decode(b"caf\xe9", Some(WINDOWS_1252)), "caf\u{e9}");
+    }
+    // the byte order mark picks the encoding and isn't shown
+    #[test]
+    fn byte_order_marks() {
+        assert_eq!(decode(b"\xef\xbb\xbfhi", None), "hi");
+        assert_eq!(decode(b"\xff\xfeh\0i\0", None), "hi");
+        assert_eq!(decode(b"\xfe\xff\0h\0i", None), "hi");
+    }
+    #[test]
+    fn binary() {
+        assert!(isbinary(b"\x7fELF\x02\x01\x01\0\0"));
+        assert!(!isbinary(b"just text\n"));
+        // UTF-16 has NULs, but a byte order mark says it is text
+        assert!(!isbinary(b"\xff\xfeh\0i\0"));
+        // only the start of a file is looked at
+        let mut late = vec![b'a'; SNIFFLEN];
+        late.push(0);
+        assert!(!isbinary(&late));
+    }
+    // a character cut off by the end of what is looked at doesn't
+    // make UTF-8 look like something else
+    #[test]
+    fn sniffing() {
+        let mut bytes = vec![b'a'; SNIFFLEN - 1];
+        bytes.extend("\u{e9}t\u{e9}".as_bytes());
+        assert_eq!(sniff(&bytes), UTF_8);
+    }
+    #[test]
+    fn hex_layout() {
+        let dump = hexdump(b"Hello, world!\n\0\xffand more");
+        assert_eq!(dump, [
+            (Tag::Pre, String::from(
+                "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|")),
+            (Tag::Pre, String::from(
+                "00000010  61 6e 64 20 6d 6f 72 65                           |and more|")),
+        ]);
+        assert_eq!(hexdump(b""), []);
+    }
+}
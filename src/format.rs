@@ -0,0 +1,301 @@
+// pager/src/format
+
+// Formats turn the bytes of a file into tagged lines. They are kept
+// in a registry, which chooses one for a file by its first bytes, then
+// by its extension, falling back to the first format registered.
+
+use crate::compress::{self, Compression};
+use crate::doc::{self, Bytes, HexDump, Lines, Source};
+use crate::encoding;
+use encoding_rs::Encoding;
+use crate::tag::{self, Tag};
+use crate::{html, man, markdown};
+use std::fmt::Debug;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+
+// how many bytes are given to DocumentFormat::magic
+const MAGICLEN: u64 = 512;
+
+pub trait DocumentFormat: Debug {
+    // the first name is the one given with --format, the rest are aliases
+    fn names(&self) -> &[&'static str];
+    // whether a file extension, without the dot, belongs to this format
+    fn extension(&self, ext: &str) -> bool;
+    // whether the first bytes of a file look like this format
+    fn magic(&self, bytes: &[u8]) -> bool {
+        let _ = bytes;
+        false
+    }
+    // whether parse is given the bytes of the file as they are,
+    // rather than decoded to UTF-8
+    fn binary(&self) -> bool {
+        false
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)>;
+    // how a line of a large file is parsed, on its own. plain text
+    // unless the format's lines stand alone
+    fn line(&self) -> fn(&str) -> (Tag, String) {
+        |line| (Tag::Text, line.to_string())
+    }
+    // the lines of a file, all parsed up front. large text files are
+    // read a line at a time instead, each line parsed with line(), so
+    // what spans lines, like tables, preformatted blocks and html,
+    // isn't formatted. text in an encoding where '\n' isn't a single
+    // byte is always read whole
+    fn source(
+        &self,
+        bytes: Bytes,
+        encoding: Option<&'static Encoding>,
+    ) -> Rc<dyn Source<Tag>> {
+        if self.binary() {
+            return Rc::new(self.parse(&bytes.whole()))
+        }
+        let guess = encoding.unwrap_or_else(|| encoding::sniff(&bytes.get(0, encoding::SNIFFLEN)));
+        match bytes.len() >= doc::LARGE && guess.is_ascii_compatible() {
+            true => Rc::new(Lines::new(bytes, guess, self.line())),
+            false => {
+                let text = encoding::decode(&bytes.whole(), encoding);
+                Rc::new(self.parse(text.as_bytes()))
+            }
+        }
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Registry {
+    formats: Vec<Rc<dyn DocumentFormat>>,
+}
+impl Registry {
+    // the fallback is used when no other format fits
+    pub fn new(fallback: Rc<dyn DocumentFormat>) -> Self {
+        Self {formats: vec![fallback]}
+    }
+    // every format this program knows about
+    pub fn builtin() -> Self {
+        let mut registry = Self::new(Rc::new(Markup));
+        registry.register(Rc::new(Gemtext));
+        registry.register(Rc::new(Markdown));
+        registry.register(Rc::new(Html));
+        registry.register(Rc::new(Man));
+        registry.register(Rc::new(Hex));
+        registry
+    }
+    // formats registered later are tried first
+    pub fn register(&mut self, format: Rc<dyn DocumentFormat>) {
+        self.formats.push(format);
+    }
+    pub fn byname(&self, name: &str) -> Option<Rc<dyn DocumentFormat>> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|f| f.names().contains(&name))
+            .cloned()
+    }
+    // names given with --format
+    pub fn names(&self) -> Vec<&'static str> {
+        self.formats.iter().filter_map(|f| f.names().first().copied()).collect()
+    }
+    // choose by magic bytes, then by extension. compressed files
+    // are looked at as they will be once decompressed
+    pub fn detect(&self, path: &str) -> Rc<dyn DocumentFormat> {
+        let mut bytes = vec![];
+        if let Ok((reader, _)) = compress::open(path) {
+            let _ = reader.take(MAGICLEN).read_to_end(&mut bytes);
+        }
+        if let Some(f) = self.formats.iter().rev().find(|f| f.magic(&bytes)) {
+            return f.clone()
+        }
+        let ext = extension(Path::new(path));
+        self.formats
+            .iter()
+            .rev()
+            .find(|f| ext.is_some_and(|e| f.extension(e)))
+            .unwrap_or(&self.formats[0])
+            .clone()
+    }
+}
+// the extension that says what a file holds. compressed files are
+// named by what's inside, and the numbers logrotate adds are skipped,
+// so app.log.3.gz is a log, while foo.1.gz is still a man page
+fn extension(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let mut parts: Vec<&str> = name.split('.').skip(1).collect();
+    if parts.last().is_some_and(|e| Compression::fromext(e).is_some()) {
+        parts.pop();
+    }
+    let ext = parts.pop()?;
+    match (ext.bytes().all(|b| b.is_ascii_digit()), parts.pop()) {
+        (true, Some(inner)) => Some(inner),
+        _ => Some(ext),
+    }
+}
+// the first bytes of a file without leading whitespace, lowercase
+fn start(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .to_lowercase()
+}
+// ".h", ".l" and ".t" lines
+#[derive(Clone, Debug)]
+pub struct Markup;
+impl DocumentFormat for Markup {
+    fn names(&self) -> &[&'static str] {
+        &["markup"]
+    }
+    fn extension(&self, _: &str) -> bool {
+        false
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)> {
+        tag::parse_doc(String::from_utf8_lossy(bytes).lines().collect())
+    }
+    fn line(&self) -> fn(&str) -> (Tag, String) {
+        tag::parse_line
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Gemtext;
+impl DocumentFormat for Gemtext {
+    fn names(&self) -> &[&'static str] {
+        &["gemtext", "gmi"]
+    }
+    fn extension(&self, ext: &str) -> bool {
+        ext == "gmi" || ext == "gemini"
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)> {
+        tag::parse_gemtext(String::from_utf8_lossy(bytes).lines().collect())
+    }
+    fn line(&self) -> fn(&str) -> (Tag, String) {
+        tag::parse_gemline
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Markdown;
+impl DocumentFormat for Markdown {
+    fn names(&self) -> &[&'static str] {
+        &["markdown", "md"]
+    }
+    fn extension(&self, ext: &str) -> bool {
+        ext == "md" || ext == "markdown"
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)> {
+        markdown::parse_markdown(String::from_utf8_lossy(bytes).lines().collect())
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Html;
+impl DocumentFormat for Html {
+    fn names(&self) -> &[&'static str] {
+        &["html"]
+    }
+    fn extension(&self, ext: &str) -> bool {
+        ext == "html" || ext == "htm"
+    }
+    fn magic(&self, bytes: &[u8]) -> bool {
+        let start = start(bytes);
+        start.starts_with("<!doctype html") || start.starts_with("<html")
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)> {
+        html::parse_html(&String::from_utf8_lossy(bytes))
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Man;
+impl DocumentFormat for Man {
+    fn names(&self) -> &[&'static str] {
+        &["man", "roff"]
+    }
+    // sections 1 to 9, with suffixes like 3p or 1ssl. numbers like
+    // 2024 or 0 aren't sections
+    fn extension(&self, ext: &str) -> bool {
+        let mut chars = ext.chars();
+        let section = chars.next().is_some_and(|c| ('1'..='9').contains(&c));
+        let suffix = chars.as_str();
+        ext == "man"
+            || section && suffix.len() <= 3 && suffix.chars().all(|c| c.is_ascii_lowercase())
+    }
+    // roff requests or overstriking
+    fn magic(&self, bytes: &[u8]) -> bool {
+        let start = start(bytes);
+        start.starts_with(".th ")
+            || start.starts_with(".\\\"")
+            || start.starts_with("'\\\"")
+            || bytes.contains(&b'\x08')
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)> {
+        man::parse_man(&String::from_utf8_lossy(bytes))
+    }
+}
+// a hex dump, for files that aren't text
+#[derive(Clone, Debug)]
+pub struct Hex;
+impl DocumentFormat for Hex {
+    fn names(&self) -> &[&'static str] {
+        &["hex"]
+    }
+    fn extension(&self, _: &str) -> bool {
+        false
+    }
+    fn magic(&self, bytes: &[u8]) -> bool {
+        encoding::isbinary(bytes)
+    }
+    fn binary(&self) -> bool {
+        true
+    }
+    fn parse(&self, bytes: &[u8]) -> Vec<(Tag, String)> {
+        encoding::hexdump(bytes)
+    }
+    fn source(&self, bytes: Bytes, _: Option<&'static Encoding>) -> Rc<dyn Source<Tag>> {
+        Rc::new(HexDump::new(bytes))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    #[test]
+    fn man_sections() {
+        for ext in ["1", "3p", "1ssl", "8", "man"] {
+            assert!(Man.extension(ext), "{}", ext);
+        }
+        for ext in ["0", "2024", "10", "1.0", "3pmxy", "1X", "log"] {
+            assert!(!Man.extension(ext), "{}", ext);
+        }
+    }
+    // a file in the temp directory, gzipped if its name says so
+    fn tempfile(dir: &TempDir, name: &str, text: &str) -> String {
+        let bytes = match name.ends_with(".gz") {
+            true => {
+                let mut gz = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                std::io::Write::write_all(&mut gz, text.as_bytes()).unwrap();
+                gz.finish().unwrap()
+            }
+            false => text.as_bytes().to_vec(),
+        };
+        dir.file(name, &bytes)
+    }
+    fn detected(name: &str, text: &str) -> &'static str {
+        let dir = TempDir::new("format");
+        let path = tempfile(&dir, name, text);
+        Registry::builtin().detect(&path).names()[0]
+    }
+    #[test]
+    fn detect_by_extension() {
+        let log = "started\n  worker 1 up\n";
+        assert_eq!(detected("app.log", log), "markup");
+        assert_eq!(detected("app.log.3", log), "markup");
+        assert_eq!(detected("app.log.3.gz", log), "markup");
+        assert_eq!(detected("data.2024", log), "markup");
+        assert_eq!(detected("foo.1.gz", "NAME\n  foo\n"), "man");
+        assert_eq!(detected("notes.md.1", "# notes\n"), "markdown");
+    }
+    // the first bytes win over the extension
+    #[test]
+    fn magic_before_extension() {
+        assert_eq!(detected("page.txt", ".TH FOO 1\n.SH NAME\n"), "man");
+        assert_eq!(detected("page.md", "<!DOCTYPE html>\n<p>hi</p>\n"), "html");
+        assert_eq!(detected("page.html", "\0\x01\x02binary"), "hex");
+    }
+}
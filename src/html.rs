@@ -0,0 +1,553 @@
+// pager/src/html
+
+// HTML is converted to tagged lines so that saved pages read like
+// any other document. Markup is stripped, headings, links, lists,
+// quotes, tables and preformatted text are kept, scripts and styles
+// are dropped.
+
+use crate::tag::{self, Tag};
+
+#[derive(Clone, Debug)]
+enum Token {
+    Open(String, Vec<(String, String)>),
+    Close(String),
+    Text(String),
+}
+pub fn parse_html(src: &str) -> Vec<(Tag, String)> {
+    let mut html = Html::new();
+    for token in tokenize(src) {
+        match token {
+            Token::Open(name, attrs) => html.open(&name, &attrs),
+            Token::Close(name) => html.close(&name),
+            Token::Text(text) => html.text(&text),
+        }
+    }
+    html.flush();
+    while let Some((Tag::Text, t)) = html.doc.last()
+        && t.is_empty()
+    {
+        html.doc.pop();
+    }
+    tag::measure_tables(&mut html.doc);
+    html.doc
+}
+// state of the conversion
+#[derive(Clone, Debug)]
+struct Html {
+    doc: Vec<(Tag, String)>,
+    // text of the line being read
+    line: String,
+    // links found in the line being read
+    links: Vec<(String, String)>,
+    // tag of the block the line belongs to
+    block: Tag,
+    // href and text of the link being read
+    link: Option<(String, String)>,
+    // enclosing lists, with the next number of ordered lists
+    lists: Vec<Option<usize>>,
+    quotes: usize,
+    pre: bool,
+    // cells of the table row being read
+    row: Option<Vec<String>>,
+    cell: Option<String>,
+    // links found in a table, shown after it
+    tablelinks: Vec<(String, String)>,
+}
+impl Html {
+    fn new() -> Self {
+        Self {
+            doc: vec![],
+            line: String::new(),
+            links: vec![],
+            block: Tag::Text,
+            link: None,
+            lists: vec![],
+            quotes: 0,
+            pre: false,
+            row: None,
+            cell: None,
+            tablelinks: vec![],
+        }
+    }
+    fn open(&mut self, name: &str, attrs: &[(String, String)]) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "title" => {
+                self.flush();
+                self.gap();
+                self.block = Tag::Heading;
+            }
+            "p" | "div" | "section" | "article" | "header" | "footer" | "nav"
+            | "main" | "aside" | "figure" | "figcaption" | "dl" | "dt" | "dd"
+            | "form" | "fieldset" | "address" | "details" | "summary" => {
+                self.flush();
+            }
+            "br" => match self.pre {
+                true => self.text("\n"),
+                false => self.flush(),
+            },
+            "hr" => {
+                self.flush();
+                self.doc.push((Tag::Text, String::from("---")));
+            }
+            "ul" | "ol" => {
+                self.flush();
+                if self.lists.is_empty() {
+                    self.gap();
+                }
+                let start = attr(attrs, "start")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(1);
+                self.lists.push(match name {
+                    "ol" => Some(start),
+                    _ => None,
+                });
+            }
+            "li" => {
+                self.flush();
+                let depth = self.lists.len().saturating_sub(1);
+                let marker = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}.", *n - 1)
+                    }
+                    _ => String::from("\u{2022}"),
+                };
+                self.block = Tag::Item(format!("{}{}", "  ".repeat(depth), marker));
+            }
+            "blockquote" => {
+                self.flush();
+                self.gap();
+                self.quotes += 1;
+            }
+            "pre" => {
+                self.flush();
+                self.gap();
+                self.pre = true;
+            }
+            "table" => {
+                self.flush();
+                self.gap();
+            }
+            "tr" => {
+                self.flush();
+                self.row = Some(vec![]);
+            }
+            "td" | "th" => {
+                self.cell = Some(String::new());
+            }
+            "a" => {
+                if let Some(href) = attr(attrs, "href") {
+                    self.link = Some((href.to_string(), String::new()));
+                }
+            }
+            "img" => {
+                if let Some(alt) = attr(attrs, "alt") {
+                    self.text(alt);
+                }
+            }
+            _ => {}
+        }
+    }
+    fn close(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "title" => {
+                self.flush();
+                self.block = Tag::Text;
+                self.gap();
+            }
+            "p" | "div" | "section" | "article" | "header" | "footer" | "nav"
+            | "main" | "aside" | "figure" | "figcaption" | "dl" | "dt" | "dd"
+            | "form" | "fieldset" | "address" | "details" | "summary" => {
+                self.flush();
+                if name == "p" {
+                    self.gap();
+                }
+            }
+            "ul" | "ol" => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block = Tag::Text;
+                    self.gap();
+                }
+            }
+            "li" => {
+                self.flush();
+                self.block = Tag::Text;
+            }
+            "blockquote" => {
+                self.flush();
+                self.quotes = self.quotes.saturating_sub(1);
+                self.gap();
+            }
+            "pre" => {
+                if !self.line.is_empty() {
+                    self.doc.push((Tag::Pre, self.line.clone()));
+                    self.line.clear();
+                }
+                self.pre = false;
+                self.gap();
+            }
+            "td" | "th" => {
+                if let (Some(row), Some(cell)) = (&mut self.row, self.cell.take()) {
+                    row.push(cell.trim().to_string());
+                }
+            }
+            "tr" => {
+                if let Some(row) = self.row.take()
+                    && !row.is_empty()
+                {
+                    self.doc.push(tag::parse_row(row));
+                }
+            }
+            "table" => {
+                let links = std::mem::take(&mut self.tablelinks);
+                for (target, label) in links {
+                    self.doc.push((Tag::Link(target), label));
+                }
+                self.gap();
+            }
+            "a" => {
+                if let Some((href, text)) = self.link.take() {
+                    let text = text.trim();
+                    let target = match href.split_once('#') {
+                        Some((file, _)) => file.to_string(),
+                        None => href.clone(),
+                    };
+                    let label = match text.is_empty() {
+                        true => href,
+                        false => text.to_string(),
+                    };
+                    if !target.is_empty() {
+                        match self.row.is_some() {
+                            true => self.tablelinks.push((target, label)),
+                            false => self.links.push((target, label)),
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    // whitespace is collapsed outside of preformatted text
+    fn text(&mut self, text: &str) {
+        if self.pre {
+            let mut parts = text.split('\n');
+            if let Some(first) = parts.next() {
+                self.line.push_str(first);
+            }
+            for part in parts {
+                self.doc.push((Tag::Pre, self.line.clone()));
+                self.line = part.to_string();
+            }
+            return
+        }
+        let mut collapsed = String::new();
+        if text.starts_with(char::is_whitespace) {
+            collapsed.push(' ');
+        }
+        collapsed.push_str(&text.split_whitespace().collect::<Vec<&str>>().join(" "));
+        if text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            collapsed.push(' ');
+        }
+        if let Some((_, linktext)) = &mut self.link {
+            linktext.push_str(&collapsed);
+        }
+        let buffer = match &mut self.cell {
+            Some(cell) => cell,
+            None => &mut self.line,
+        };
+        if buffer.is_empty() || buffer.ends_with(' ') {
+            buffer.push_str(collapsed.trim_start());
+        } else {
+            buffer.push_str(&collapsed);
+        }
+    }
+    // push the line being read
+    fn flush(&mut self) {
+        let text = self.line.trim().to_string();
+        let links = std::mem::take(&mut self.links);
+        self.line.clear();
+        if text.is_empty() && links.is_empty() {
+            return
+        }
+        let tag = match (&self.block, self.quotes) {
+            (Tag::Text, 1..) => Tag::Quote,
+            (block, _) => block.clone(),
+        };
+        tag::pushline(&mut self.doc, tag, &text, links);
+        // later lines of a list item line up under its text
+        if let Tag::Item(marker) = &self.block {
+            self.block = Tag::Item(" ".repeat(marker.chars().count()));
+        }
+    }
+    // separate blocks with an empty line
+    fn gap(&mut self) {
+        match self.doc.last() {
+            None => {}
+            Some((Tag::Text, t)) if t.is_empty() => {}
+            _ => self.doc.push((Tag::Text, String::new())),
+        }
+    }
+}
+fn attr<'a>(attrs: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    attrs.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+}
+// split the source into tags and text, skipping comments,
+// doctypes and the contents of scripts and styles
+fn tokenize(src: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut rest = src;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(entities(rest)));
+            break
+        };
+        if start > 0 {
+            tokens.push(Token::Text(entities(&rest[..start])));
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = match comment.find("-->") {
+                Some(end) => &comment[end + 3..],
+                None => "",
+            };
+            continue
+        }
+        let Some(end) = tagend(rest) else {
+            tokens.push(Token::Text(entities(rest)));
+            break
+        };
+        let inner = &rest[1..end];
+        rest = &rest[end + 1..];
+        if inner.starts_with('!') || inner.starts_with('?') {
+            continue
+        }
+        if let Some(name) = inner.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim().to_lowercase()));
+            continue
+        }
+        let (name, attrs) = parse_tag(inner);
+        if name.is_empty() {
+            tokens.push(Token::Text(format!("<{}>", inner)));
+            continue
+        }
+        // skip to the end of raw text elements
+        if ["script", "style", "template"].contains(&name.as_str()) {
+            let close = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(i) => {
+                    let after = &rest[i..];
+                    after.find('>').map(|j| &after[j + 1..]).unwrap_or("")
+                }
+                None => "",
+            };
+            continue
+        }
+        tokens.push(Token::Open(name, attrs));
+    }
+    tokens
+}
+// index of the '>' that ends the tag at the start of src,
+// ignoring any inside quoted attribute values
+fn tagend(src: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in src.char_indices() {
+        match (quote, c) {
+            (None, '>') => return Some(i),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+// the lowercase name and the attributes of an opening tag
+fn parse_tag(inner: &str) -> (String, Vec<(String, String)>) {
+    let inner = inner.trim_end_matches('/');
+    let name: String = inner
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    let chars: Vec<char> = inner.chars().skip(name.chars().count()).collect();
+    let mut attrs = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue
+        }
+        let key: String = chars[i..]
+            .iter()
+            .take_while(|c| !c.is_whitespace() && **c != '=')
+            .collect();
+        i += key.chars().count().max(1);
+        let mut value = String::new();
+        if chars.get(i) == Some(&'=') {
+            i += 1;
+            match chars.get(i) {
+                Some(q) if *q == '"' || *q == '\'' => {
+                    value = chars[i + 1..].iter().take_while(|c| *c != q).collect();
+                    i += value.chars().count() + 2;
+                }
+                _ => {
+                    value = chars[i..].iter().take_while(|c| !c.is_whitespace()).collect();
+                    i += value.chars().count();
+                }
+            }
+        }
+        attrs.push((key.to_lowercase(), entities(&value)));
+    }
+    (name, attrs)
+}
+// replace character references with the characters they name
+fn entities(text: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(';').filter(|e| *e < 12);
+        let named = end.and_then(|e| entity(&rest[1..e]));
+        match (end, named) {
+            (Some(e), Some(c)) => {
+                decoded.push(c);
+                rest = &rest[e + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+fn entity(name: &str) -> Option<char> {
+    if let Some(code) = name.strip_prefix('#') {
+        let n = match code.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => code.parse().ok()?,
+        };
+        return char::from_u32(n)
+    }
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        "ndash" => Some('\u{2013}'),
+        "mdash" => Some('\u{2014}'),
+        "hellip" => Some('\u{2026}'),
+        "lsquo" => Some('\u{2018}'),
+        "rsquo" => Some('\u{2019}'),
+        "ldquo" => Some('\u{201c}'),
+        "rdquo" => Some('\u{201d}'),
+        "laquo" => Some('\u{ab}'),
+        "raquo" => Some('\u{bb}'),
+        "copy" => Some('\u{a9}'),
+        "reg" => Some('\u{ae}'),
+        "middot" => Some('\u{b7}'),
+        "bull" => Some('\u{2022}'),
+        _ => None,
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> String {
+        String::from(s)
+    }
+    #[test]
+    fn headings() {
+        let doc = parse_html("<h1>Title</h1><p>some\n   text</p><h2>Next</h2>");
+        assert_eq!(doc, [
+            (Tag::Heading, text("Title")),
+            (Tag::Text, text("")),
+            (Tag::Text, text("some text")),
+            (Tag::Text, text("")),
+            (Tag::Heading, text("Next")),
+        ]);
+    }
+    // the fragment is dropped from links, links only to a place
+    // in the same page are kept as text
+    #[test]
+    fn links() {
+        let doc = parse_html(concat!(
+            "<p><a href=\"guide.html\">guide</a></p>",
+            "<p>see <a href=\"faq.html#install\">the faq</a> first</p>",
+            "<p><a href=\"#top\">top</a></p>",
+        ));
+        assert_eq!(doc, [
+            (Tag::Link(text("guide.html")), text("guide")),
+            (Tag::Text, text("")),
+            (Tag::Text, text("see the faq first")),
+            (Tag::Link(text("faq.html")), text("the faq")),
+            (Tag::Text, text("")),
+            (Tag::Text, text("top")),
+        ]);
+    }
+    #[test]
+    fn lists() {
+        let doc = parse_html(concat!(
+            "<ul><li>one<ul><li>nested</li></ul></li></ul>",
+            "<ol start=\"3\"><li>three</li><li>four</li></ol>",
+        ));
+        assert_eq!(doc, [
+            (Tag::Item(text("\u{2022}")), text("one")),
+            (Tag::Item(text("  \u{2022}")), text("nested")),
+            (Tag::Text, text("")),
+            (Tag::Item(text("3.")), text("three")),
+            (Tag::Item(text("4.")), text("four")),
+        ]);
+    }
+    #[test]
+    fn pre_keeps_whitespace() {
+        let doc = parse_html("<pre>fn main() {\n    go();\n}</pre><p>a  b</p>");
+        assert_eq!(doc, [
+            (Tag::Pre, text("fn main() {")),
+            (Tag::Pre, text("    go();")),
+            (Tag::Pre, text("}")),
+            (Tag::Text, text("")),
+            (Tag::Text, text("a b")),
+        ]);
+    }
+    #[test]
+    fn scripts_and_styles_are_dropped() {
+        let doc = parse_html(concat!(
+            "<style>p { color: red }</style>",
+            "<p>shown</p>",
+            "<SCRIPT>if (a < b) { x() }</SCRIPT><!-- <p>hidden</p> -->",
+        ));
+        assert_eq!(doc, [(Tag::Text, text("shown"))]);
+    }
+    #[test]
+    fn character_references() {
+        let doc = parse_html("<p>a &amp; b &lt;c&gt; &#65;&#x42; &mdash; &bogus; & d</p>");
+        assert_eq!(doc, [(Tag::Text, text("a & b <c> AB \u{2014} &bogus; & d"))]);
+    }
+    // links in a table are shown after it
+    #[test]
+    fn tables() {
+        let doc = parse_html(concat!(
+            "<table><tr><th>name</th><th>size</th></tr>",
+            "<tr><td><a href=\"a.html\">a</a></td><td>10</td></tr></table>",
+        ));
+        let rows: Vec<(Vec<String>, Vec<usize>, bool)> = doc[..2]
+            .iter()
+            .map(|(t, _)| match t {
+                Tag::Row(r) => (r.cells.clone(), r.widths.clone(), r.header),
+                _ => panic!("{:?}", t),
+            })
+            .collect();
+        assert_eq!(rows, [
+            (vec![text("name"), text("size")], vec![4, 4], true),
+            (vec![text("a"), text("10")], vec![4, 4], false),
+        ]);
+        assert_eq!(doc[2..], [(Tag::Link(text("a.html")), text("a"))]);
+    }
+}
@@ -0,0 +1,421 @@
+// pager/src/keys
+
+// Keys are bound to named commands rather than handled directly, so
+// the bindings can come from a preset or a config file. A binding is
+// a sequence of one or more keys, each with its modifiers. Digits
+// typed before a sequence make a count, as in "20j".
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+// presets that can be given with --keys, the first is the default
+pub const PRESETS: [&str; 3] = ["pager", "less", "vi"];
+
+// what a key sequence does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    HalfPageDown,
+    HalfPageUp,
+    Top,
+    Bottom,
+    GoToLine,
+    ScrollDown,
+    ScrollUp,
+    Center,
+    LineToTop,
+    LineToBottom,
+    ScrollLeft,
+    ScrollRight,
+    ToggleWrap,
+    ToggleNumbers,
+    PreviousTab,
+    NextTab,
+    CloseTab,
+    OpenPath,
+    Select,
+    Reload,
+    Quit,
+}
+impl Command {
+    pub const ALL: [Self; 25] = [
+        Self::Down,
+        Self::Up,
+        Self::PageDown,
+        Self::PageUp,
+        Self::HalfPageDown,
+        Self::HalfPageUp,
+        Self::Top,
+        Self::Bottom,
+        Self::GoToLine,
+        Self::ScrollDown,
+        Self::ScrollUp,
+        Self::Center,
+        Self::LineToTop,
+        Self::LineToBottom,
+        Self::ScrollLeft,
+        Self::ScrollRight,
+        Self::ToggleWrap,
+        Self::ToggleNumbers,
+        Self::PreviousTab,
+        Self::NextTab,
+        Self::CloseTab,
+        Self::OpenPath,
+        Self::Select,
+        Self::Reload,
+        Self::Quit,
+    ];
+    // names used in config files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Down => "down",
+            Self::Up => "up",
+            Self::PageDown => "page-down",
+            Self::PageUp => "page-up",
+            Self::HalfPageDown => "half-page-down",
+            Self::HalfPageUp => "half-page-up",
+            Self::Top => "top",
+            Self::Bottom => "bottom",
+            Self::GoToLine => "go-to-line",
+            Self::ScrollDown => "scroll-down",
+            Self::ScrollUp => "scroll-up",
+            Self::Center => "center",
+            Self::LineToTop => "line-to-top",
+            Self::LineToBottom => "line-to-bottom",
+            Self::ScrollLeft => "scroll-left",
+            Self::ScrollRight => "scroll-right",
+            Self::ToggleWrap => "toggle-wrap",
+            Self::ToggleNumbers => "toggle-numbers",
+            Self::PreviousTab => "previous-tab",
+            Self::NextTab => "next-tab",
+            Self::CloseTab => "close-tab",
+            Self::OpenPath => "open-path",
+            Self::Select => "select",
+            Self::Reload => "reload",
+            Self::Quit => "quit",
+        }
+    }
+    pub fn byname(name: &str) -> Option<Self> {
+        Self::ALL.iter().find(|c| c.name() == name).copied()
+    }
+}
+// a key and its modifiers. shift is part of the character for
+// character keys, so 'G' is shift-g
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+impl Key {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Self {code, modifiers}
+    }
+    // "j", "G", "C-e", "M-x", "S-Tab", "PageDown", "Space"...
+    pub fn parse(name: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = name;
+        // "C-" on its own is no key, "-" is
+        while rest.len() > 2 {
+            modifiers |= match rest.get(..2) {
+                Some("C-") => KeyModifiers::CONTROL,
+                Some("M-") => KeyModifiers::ALT,
+                Some("S-") => KeyModifiers::SHIFT,
+                _ => break,
+            };
+            rest = &rest[2..];
+        }
+        let code = match rest {
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Delete" => KeyCode::Delete,
+            "Insert" => KeyCode::Insert,
+            "Space" => KeyCode::Char(' '),
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            _ => match rest.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => {
+                    let mut chars = rest.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => KeyCode::Char(c),
+                        _ => return None,
+                    }
+                }
+            },
+        };
+        Some(Self::new(code, modifiers))
+    }
+}
+impl From<&KeyEvent> for Key {
+    fn from(event: &KeyEvent) -> Self {
+        Self::new(event.code, event.modifiers)
+    }
+}
+// key sequences and their commands, and the keys of a sequence
+// typed so far with the count before it
+#[derive(Clone, Debug, Default)]
+pub struct Bindings {
+    table: Vec<(Vec<Key>, Command)>,
+    pending: Vec<Key>,
+    count: Option<usize>,
+}
+impl Bindings {
+    pub fn preset(name: &str) -> Option<Self> {
+        let table: &[(&str, Command)] = match name {
+            // the layout this pager started with
+            "pager" => &[
+                ("i", Command::Down),
+                ("o", Command::Up),
+                ("PageDown", Command::PageDown),
+                ("PageUp", Command::PageUp),
+                ("C-d", Command::HalfPageDown),
+                ("C-u", Command::HalfPageUp),
+                ("Home", Command::Top),
+                ("End", Command::Bottom),
+                ("g", Command::GoToLine),
+                ("C-e", Command::ScrollDown),
+                ("C-y", Command::ScrollUp),
+                ("z", Command::Center),
+                ("t", Command::LineToTop),
+                ("b", Command::LineToBottom),
+                ("Left", Command::ScrollLeft),
+                ("Right", Command::ScrollRight),
+                ("w", Command::ToggleWrap),
+                ("#", Command::ToggleNumbers),
+                ("e", Command::PreviousTab),
+                ("n", Command::NextTab),
+                ("v", Command::CloseTab),
+                ("p", Command::OpenPath),
+                ("r", Command::Reload),
+                ("Enter", Command::Select),
+            ],
+            "less" => &[
+                ("j", Command::Down),
+                ("C-n", Command::Down),
+                ("Down", Command::Down),
+                ("k", Command::Up),
+                ("C-p", Command::Up),
+                ("Up", Command::Up),
+                ("e", Command::ScrollDown),
+                ("C-e", Command::ScrollDown),
+                ("y", Command::ScrollUp),
+                ("C-y", Command::ScrollUp),
+                ("Left", Command::ScrollLeft),
+                ("Right", Command::ScrollRight),
+                ("- S", Command::ToggleWrap),
+                ("- N", Command::ToggleNumbers),
+                ("Space", Command::PageDown),
+                ("f", Command::PageDown),
+                ("C-f", Command::PageDown),
+                ("C-v", Command::PageDown),
+                ("PageDown", Command::PageDown),
+                ("b", Command::PageUp),
+                ("C-b", Command::PageUp),
+                ("M-v", Command::PageUp),
+                ("PageUp", Command::PageUp),
+                ("d", Command::HalfPageDown),
+                ("C-d", Command::HalfPageDown),
+                ("u", Command::HalfPageUp),
+                ("C-u", Command::HalfPageUp),
+                ("g", Command::Top),
+                ("<", Command::Top),
+                ("Home", Command::Top),
+                ("G", Command::Bottom),
+                (">", Command::Bottom),
+                ("End", Command::Bottom),
+                ("M-g", Command::GoToLine),
+                (": p", Command::PreviousTab),
+                (": n", Command::NextTab),
+                (": d", Command::CloseTab),
+                (": e", Command::OpenPath),
+                ("R", Command::Reload),
+                ("Enter", Command::Select),
+                ("q", Command::Quit),
+                ("Q", Command::Quit),
+            ],
+            "vi" => &[
+                ("j", Command::Down),
+                ("C-n", Command::Down),
+                ("Down", Command::Down),
+                ("k", Command::Up),
+                ("C-p", Command::Up),
+                ("Up", Command::Up),
+                ("C-f", Command::PageDown),
+                ("PageDown", Command::PageDown),
+                ("C-b", Command::PageUp),
+                ("PageUp", Command::PageUp),
+                ("C-d", Command::HalfPageDown),
+                ("C-u", Command::HalfPageUp),
+                ("g g", Command::Top),
+                ("Home", Command::Top),
+                ("G", Command::Bottom),
+                ("End", Command::Bottom),
+                ("M-g", Command::GoToLine),
+                ("C-e", Command::ScrollDown),
+                ("C-y", Command::ScrollUp),
+                ("z z", Command::Center),
+                ("z t", Command::LineToTop),
+                ("z b", Command::LineToBottom),
+                ("Left", Command::ScrollLeft),
+                ("Right", Command::ScrollRight),
+                ("z H", Command::ScrollLeft),
+                ("z L", Command::ScrollRight),
+                ("z w", Command::ToggleWrap),
+                ("z n", Command::ToggleNumbers),
+                ("g T", Command::PreviousTab),
+                ("g t", Command::NextTab),
+                (": q", Command::CloseTab),
+                (": e", Command::OpenPath),
+                ("R", Command::Reload),
+                ("Enter", Command::Select),
+                ("Z Z", Command::Quit),
+                ("Z Q", Command::Quit),
+            ],
+            _ => return None,
+        };
+        let mut bindings = Self::default();
+        for (keys, command) in table.iter() {
+            let keys = keys.split(' ').filter_map(Key::parse).collect();
+            bindings.bind(keys, *command);
+        }
+        Some(bindings)
+    }
+    // replaces what the sequence was bound to
+    pub fn bind(&mut self, keys: Vec<Key>, command: Command) {
+        self.unbind(&keys);
+        self.table.push((keys, command));
+    }
+    pub fn unbind(&mut self, keys: &[Key]) {
+        self.table.retain(|(k, _)| k != keys);
+    }
+    // the command once a sequence is complete. a key that can't
+    // continue the sequence starts a new one. digits that aren't
+    // bound make the count, which a leading 0 can't start
+    pub fn feed(&mut self, key: Key) -> Option<Command> {
+        if let Some(digit) = self.digit(key) {
+            self.count = Some(self.count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            return None
+        }
+        self.pending.push(key);
+        if let Some((_, command)) = self.table.iter().find(|(k, _)| *k == self.pending) {
+            self.pending.clear();
+            return Some(*command)
+        }
+        if self.table.iter().any(|(k, _)| k.starts_with(&self.pending)) {
+            return None
+        }
+        let started = self.pending.len() > 1;
+        self.pending.clear();
+        match started {
+            true => self.feed(key),
+            false => {
+                self.count = None;
+                None
+            }
+        }
+    }
+    // the count typed before the last command, once
+    pub fn takecount(&mut self) -> Option<usize> {
+        self.count.take()
+    }
+    fn digit(&self, key: Key) -> Option<usize> {
+        let KeyCode::Char(c) = key.code else {
+            return None
+        };
+        let digit = c.to_digit(10)? as usize;
+        let bound = self.table.iter().any(|(k, _)| k.first() == Some(&key));
+        match self.pending.is_empty() && key.modifiers.is_empty() && !bound {
+            true => (digit > 0 || self.count.is_some()).then_some(digit),
+            false => None,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str) -> Key {
+        Key::parse(name).unwrap()
+    }
+    // the command, if any, after each key of a sequence
+    fn feed(bindings: &mut Bindings, keys: &str) -> Vec<Option<Command>> {
+        keys.split(' ').map(|k| bindings.feed(key(k))).collect()
+    }
+    #[test]
+    fn parse_names() {
+        assert_eq!(key("j"), Key::new(KeyCode::Char('j'), KeyModifiers::NONE));
+        assert_eq!(key("G"), Key::new(KeyCode::Char('G'), KeyModifiers::SHIFT));
+        assert_eq!(key("C-e"), Key::new(KeyCode::Char('e'), KeyModifiers::CONTROL));
+        let both = KeyModifiers::CONTROL | KeyModifiers::ALT;
+        assert_eq!(key("C-M-x"), Key::new(KeyCode::Char('x'), both));
+        assert_eq!(key("S-Tab"), Key::new(KeyCode::Tab, KeyModifiers::SHIFT));
+        assert_eq!(key("Space"), Key::new(KeyCode::Char(' '), KeyModifiers::NONE));
+        assert_eq!(key("F12"), Key::new(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(key("-"), Key::new(KeyCode::Char('-'), KeyModifiers::NONE));
+        for name in ["", "C-", "F13", "jk", "C-jk", "Pageup"] {
+            assert_eq!(Key::parse(name), None, "{}", name);
+        }
+    }
+    #[test]
+    fn pager_keys() {
+        let mut pager = Bindings::preset("pager").unwrap();
+        assert_eq!(feed(&mut pager, "C-d C-u"), [Some(Command::HalfPageDown), Some(Command::HalfPageUp)]);
+        assert_eq!(feed(&mut pager, "t b z"), [
+            Some(Command::LineToTop),
+            Some(Command::LineToBottom),
+            Some(Command::Center),
+        ]);
+    }
+    #[test]
+    fn sequences() {
+        let mut vi = Bindings::preset("vi").unwrap();
+        assert_eq!(feed(&mut vi, "g t"), [None, Some(Command::NextTab)]);
+        assert_eq!(feed(&mut vi, "g g"), [None, Some(Command::Top)]);
+        // a key that can't go on starts a sequence of its own
+        assert_eq!(feed(&mut vi, "z j"), [None, Some(Command::Down)]);
+        assert_eq!(feed(&mut vi, "g z z"), [None, None, Some(Command::Center)]);
+        // keys bound to nothing are dropped
+        assert_eq!(feed(&mut vi, "x j"), [None, Some(Command::Down)]);
+    }
+    #[test]
+    fn counts() {
+        let mut vi = Bindings::preset("vi").unwrap();
+        assert_eq!(feed(&mut vi, "2 0 j"), [None, None, Some(Command::Down)]);
+        assert_eq!(vi.takecount(), Some(20));
+        assert_eq!(vi.takecount(), None);
+        // the count carries over a sequence
+        assert_eq!(feed(&mut vi, "3 g t"), [None, None, Some(Command::NextTab)]);
+        assert_eq!(vi.takecount(), Some(3));
+        // a leading 0 is no count, a key bound to nothing drops one
+        assert_eq!(feed(&mut vi, "0 j"), [None, Some(Command::Down)]);
+        assert_eq!(vi.takecount(), None);
+        assert_eq!(feed(&mut vi, "5 x j"), [None, None, Some(Command::Down)]);
+        assert_eq!(vi.takecount(), None);
+    }
+    // digits that are bound are commands, not counts
+    #[test]
+    fn bound_digits() {
+        let mut vi = Bindings::preset("vi").unwrap();
+        vi.bind(vec![key("1")], Command::Top);
+        assert_eq!(feed(&mut vi, "1"), [Some(Command::Top)]);
+        assert_eq!(feed(&mut vi, "2 1"), [None, Some(Command::Top)]);
+        assert_eq!(vi.takecount(), Some(2));
+        vi.unbind(&[key("1")]);
+        assert_eq!(feed(&mut vi, "1 j"), [None, Some(Command::Down)]);
+        assert_eq!(vi.takecount(), Some(1));
+    }
+}
@@ -0,0 +1,27 @@
+// pager/src/lib
+
+// The document viewer as a library. Formats parse files into tagged
+// lines, util and tag lay them out for a width, and a TabMgr shows
+// them in tabs, drawn into a Screen that is flushed to a Backend.
+// The pager binary is a thin front end over this.
+
+pub mod backend;
+pub mod cat;
+pub mod compress;
+pub mod config;
+pub mod doc;
+pub mod encoding;
+pub mod format;
+pub mod keys;
+pub mod screen;
+pub mod tabs;
+pub mod tag;
+pub mod theme;
+pub mod ui;
+pub mod util;
+pub mod widget;
+
+mod dir;
+mod html;
+mod man;
+mod markdown;
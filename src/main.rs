@@ -1,27 +1,246 @@
 // pager/src/main
 
-#![allow(unused_variables)]
-#![allow(dead_code)]
+use pager::ui::UI;
+use pager::backend::Terminal;
+use pager::cat;
+use pager::config::{ColorMode, Numbers, Options, Start};
+use pager::doc::{Cache, Source};
+use pager::format::Registry;
+use pager::keys::{Bindings, PRESETS};
+use pager::tag::Tag;
+use pager::theme::{Depth, THEMES};
+use crossterm::{QueueableCommand, terminal, cursor, event};
+use std::io::{self, stdout, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::env;
+use encoding_rs::Encoding;
 
-mod ui;
-mod util;
-mod tag;
-mod widget;
-mod tabs;
+// exit codes, as most tools use them
+const EXIT_ERROR: u8 = 1;
+const EXIT_USAGE: u8 = 2;
 
-use crate::ui::UI;
-use crossterm::{QueueableCommand, terminal, cursor, event};
-use std::io::{self, stdout, Write};
-use std::{env};
-
-fn main() -> io::Result<()> {
-    // set up
-    let args: Vec<String> = env::args().collect();
-    let Some(path) = args.get(1) else {
-        panic!("supply path as arg")
+// what the arguments ask for
+enum Command {
+    // files, options, and what was wrong with the config file
+    Show(Vec<String>, Box<Options>, Vec<String>),
+    Help,
+    Version,
+}
+
+fn main() -> ExitCode {
+    let formats = Registry::builtin();
+    let (paths, options, warnings) = match parseargs(env::args().skip(1), &formats) {
+        Ok(Command::Show(paths, options, warnings)) => (paths, *options, warnings),
+        Ok(Command::Help) => {
+            print!("{}", usage(&formats));
+            return ExitCode::SUCCESS
+        }
+        Ok(Command::Version) => {
+            println!("pager {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("pager: {}\ntry 'pager --help'", e);
+            return ExitCode::from(EXIT_USAGE)
+        }
+    };
+    // bad settings are left at their defaults
+    for w in warnings.iter() {
+        eprintln!("pager: {}", w);
+    }
+    match run(&paths, formats, options, &warnings) {
+        Ok(()) => ExitCode::SUCCESS,
+        // whatever was reading the output has stopped
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("pager: {}", e);
+            ExitCode::from(EXIT_ERROR)
+        }
+    }
+}
+fn usage(formats: &Registry) -> String {
+    format!(
+"usage: pager [options] [+line | +/pattern] file...
+
+Show each file in a tab. +line starts at a line, + alone at the end,
+and +/pattern at the first line containing the pattern. When output
+isn't a terminal, the files are printed instead.
+
+options:
+  -f, --format NAME     parse every file as NAME: {}
+  -e, --encoding NAME   decode every file as NAME, e.g. latin1
+  -w, --wrap            wrap long lines (default)
+  -S, --no-wrap         cut long lines at the edge of the screen
+  -N, --line-numbers    number lines in a gutter
+  -F, --quit-if-one-screen
+                        print the files and exit if they fit on one screen
+      --color WHEN      color printed files: auto, always or never
+  -t, --theme NAME      colors to use: {}
+  -k, --keys NAME       key bindings to start from: {}
+  -c, --config PATH     read settings from PATH
+  -h, --help            show this help
+  -V, --version         show the version
+",
+        formats.names().join(", "),
+        THEMES.join(", "),
+        PRESETS.join(", "))
+}
+// options can be given as "--name value" or "--name=value"
+fn parseargs(
+    args: impl Iterator<Item = String>,
+    formats: &Registry,
+) -> Result<Command, String> {
+    let args: Vec<String> = args.collect();
+    // the config file is read first, so the command line overrides it
+    let mut options = Options::default();
+    let mut warnings = vec![];
+    if let Some(path) = configpath(&args).or_else(Options::configpath) {
+        warnings = options.load(&path);
+        options.config = Some(path);
+    }
+    let mut args = args.into_iter();
+    let mut paths = vec![];
+    let mut dashdash = false;
+    while let Some(arg) = args.next() {
+        if dashdash || !arg.starts_with(['-', '+']) {
+            paths.push(arg);
+            continue
+        }
+        let (name, mut value) = match arg.split_once('=') {
+            Some((n, v)) if arg.starts_with("--") => (n.to_string(), Some(v.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = |name: &str| match value.take().or_else(|| args.next()) {
+            Some(v) => Ok(v),
+            None => Err(format!("{} needs a value", name)),
+        };
+        match name.as_str() {
+            "--" => dashdash = true,
+            "-h" | "--help" => return Ok(Command::Help),
+            "-V" | "--version" => return Ok(Command::Version),
+            "-w" | "--wrap" => options.wrap = true,
+            "-S" | "--no-wrap" => options.wrap = false,
+            "-N" | "--line-numbers" => options.numbers = Numbers::Absolute,
+            "-F" | "--quit-if-one-screen" => options.quitifonescreen = true,
+            "--color" => {
+                let name = value(&name)?;
+                let Some(c) = ColorMode::parse(&name) else {
+                    return Err(format!("unknown color mode: {}, try auto, always, never", name))
+                };
+                options.color = c;
+            }
+            // force a format instead of guessing from the extension
+            "-f" | "--format" => {
+                let name = value(&name)?;
+                let Some(f) = formats.byname(&name) else {
+                    return Err(format!(
+                        "unknown format: {}, try {}", name, formats.names().join(", ")))
+                };
+                options.format = Some(f);
+            }
+            // decode files with this instead of guessing
+            "-e" | "--encoding" => {
+                let name = value(&name)?;
+                let Some(e) = Encoding::for_label(name.as_bytes()) else {
+                    return Err(format!("unknown encoding: {}", name))
+                };
+                options.encoding = Some(e);
+            }
+            "-t" | "--theme" => {
+                let name = value(&name)?;
+                if !THEMES.contains(&name.as_str()) {
+                    return Err(format!("unknown theme: {}, try {}", name, THEMES.join(", ")))
+                }
+                options.theme = name;
+            }
+            // already read
+            "-c" | "--config" => {
+                value(&name)?;
+            }
+            "-k" | "--keys" => {
+                let name = value(&name)?;
+                if Bindings::preset(&name).is_none() {
+                    return Err(format!("unknown keys: {}, try {}", name, PRESETS.join(", ")))
+                }
+                options.keys = name;
+            }
+            _ => match Start::parse(&arg) {
+                Some(start) => options.start = start,
+                None => return Err(format!("unknown option: {}", arg)),
+            },
+        }
+    }
+    if paths.is_empty() {
+        return Err(String::from("no files given"))
+    }
+    Ok(Command::Show(paths, Box::new(options), warnings))
+}
+// print the documents instead of paging them when stdout isn't a
+// terminal, or when asked to and they fit on one screen
+fn cat(paths: &[String], formats: &Registry, options: &Options) -> io::Result<bool> {
+    let tty = io::stdout().is_terminal();
+    if tty && !options.quitifonescreen {
+        return Ok(false)
+    }
+    let mut cache = Cache::new(options.encoding);
+    let mut docs = vec![];
+    for path in paths.iter() {
+        let format = options.formatfor(formats, path);
+        let doc = cache
+            .open(path, &format)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+        docs.push(doc);
+    }
+    let sources: Vec<&dyn Source<Tag>> = docs.iter().map(|d| d.source.as_ref()).collect();
+    // the last row is left for the shell prompt
+    let size = match tty {
+        true => Some(terminal::size()?),
+        false => None,
     };
+    if let Some((w, h)) = size
+        && !cat::fits(&sources, w, usize::from(h).saturating_sub(1), options)
+    {
+        return Ok(false)
+    }
+    let depth = options.depth.unwrap_or_else(Depth::detect);
+    let colors = cat::colored(options.color, tty, depth);
+    let theme = options.palette();
+    let theme = colors.then_some(&theme);
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for source in sources.iter() {
+        cat::print(&mut out, *source, size.map(|(w, _)| w), options, theme)?;
+    }
+    Ok(true)
+}
+// the value of the last --config option
+fn configpath(args: &[String]) -> Option<PathBuf> {
+    let mut path = None;
+    let mut args = args.iter().take_while(|a| *a != "--");
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--config=") {
+            Some(p) => path = Some(PathBuf::from(p)),
+            None if arg == "-c" || arg == "--config" => path = args.next().map(PathBuf::from),
+            None => {}
+        }
+    }
+    path
+}
+// the terminal is put back as it was, even when something fails
+fn run(
+    paths: &[String],
+    formats: Registry,
+    options: Options,
+    warnings: &[String],
+) -> io::Result<()> {
+    if cat(paths, &formats, &options)? {
+        return Ok(())
+    }
     let (w, h) = terminal::size()?;
-    let mut ui = UI::new(path, w, h);
+    let mut ui = UI::new(paths, formats, options, w, h)?;
+    for w in warnings.iter() {
+        ui.notify(w);
+    }
 
     let mut stdout = stdout();
     terminal::enable_raw_mode()?;
@@ -31,18 +250,86 @@ fn main() -> io::Result<()> {
         .queue(cursor::Show)?;
     stdout.flush()?;
 
-    ui.view(&stdout)?;
-
-    // main loop
-    while !ui.quit() {
-        if ui.update(event::read()?) {
-            ui.view(&stdout)?;
+    let mut backend = Terminal::new(io::stdout());
+    let result = (|| {
+        ui.view(&mut backend)?;
+        // main loop
+        while !ui.quit() {
+            if ui.update(event::read()?) {
+                ui.view(&mut backend)?;
+            }
         }
-    }
+        Ok(())
+    })();
 
     // clean up
     terminal::disable_raw_mode()?;
-    stdout.queue(terminal::LeaveAlternateScreen)?;
+    stdout.queue(terminal::LeaveAlternateScreen)?.queue(cursor::Show)?;
     stdout.flush()?;
-    Ok(())
+    result
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // arguments after an empty config file, so settings of the user
+    // running the tests don't count
+    fn parse(args: &str) -> Result<Command, String> {
+        let args = format!("--config=/dev/null {}", args);
+        parseargs(args.split(' ').map(String::from), &Registry::builtin())
+    }
+    fn show(args: &str) -> (Vec<String>, Options) {
+        match parse(args) {
+            Ok(Command::Show(paths, options, _)) => (paths, *options),
+            Ok(_) => panic!("{} shows nothing", args),
+            Err(e) => panic!("{}", e),
+        }
+    }
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+    #[test]
+    fn start() {
+        assert_eq!(show("+42 a").1.start, Start::Line(42));
+        assert_eq!(show("a +/main").1.start, Start::Search(String::from("main")));
+        assert_eq!(show("+ a").1.start, Start::Line(usize::MAX));
+        assert_eq!(show("a").1.start, Start::Top);
+    }
+    #[test]
+    fn values() {
+        let (paths, options) = show("--theme=light -k vi --format=md -S -N a b");
+        assert_eq!(paths, strings(&["a", "b"]));
+        assert_eq!(options.theme, "light");
+        assert_eq!(options.keys, "vi");
+        assert_eq!(options.format.map(|f| f.names()[0]), Some("markdown"));
+        assert!(!options.wrap);
+        assert_eq!(options.numbers, Numbers::Absolute);
+    }
+    // everything after "--" is a file
+    #[test]
+    fn dashdash() {
+        let (paths, options) = show("-- -S +3 --config=x a");
+        assert_eq!(paths, strings(&["-S", "+3", "--config=x", "a"]));
+        assert!(options.wrap);
+        assert_eq!(options.start, Start::Top);
+    }
+    // main exits with EXIT_USAGE on any of these
+    #[test]
+    fn usage_errors() {
+        let error = |args| parse(args).err().unwrap_or_default();
+        assert_eq!(error("--frobnicate a"), "unknown option: --frobnicate");
+        assert_eq!(error("+x a"), "unknown option: +x");
+        assert_eq!(error("a --theme"), "--theme needs a value");
+        assert!(error("--keys=emacs a").starts_with("unknown keys: emacs"));
+        assert_eq!(error("-S"), "no files given");
+        assert!(matches!(parse("-h a"), Ok(Command::Help)));
+    }
+    // the last --config counts, up to "--"
+    #[test]
+    fn config_path() {
+        let args = strings(&["-c", "a", "x", "--config=b", "--", "--config=c"]);
+        assert_eq!(configpath(&args), Some(PathBuf::from("b")));
+        assert_eq!(configpath(&strings(&["--config", "a"])), Some(PathBuf::from("a")));
+        assert_eq!(configpath(&strings(&["x", "--", "-c", "a"])), None);
+    }
 }
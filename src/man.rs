@@ -0,0 +1,566 @@
+// pager/src/man
+
+// Man pages, either as roff source or as the overstruck output of
+// man ("x\bx" is bold, "_\bx" is underlined). Section headers become
+// headings, and the references under SEE ALSO become links to the
+// local man pages they name.
+
+use crate::tag::{self, Tag};
+use std::{env, fs};
+use std::path::Path;
+
+// how far body text is indented, as man does it
+const INDENT: usize = 7;
+
+pub fn parse_man(src: &str) -> Vec<(Tag, String)> {
+    match src.contains('\x08') || src.contains("\x1b[") {
+        true => parse_overstrike(src.lines().collect()),
+        false => parse_roff(src.lines().collect()),
+    }
+}
+// output of man, formatted for an 80 column terminal
+pub fn parse_overstrike(lines: Vec<&str>) -> Vec<(Tag, String)> {
+    let mut vec = vec![];
+    let mut section = String::new();
+    for line in lines.iter() {
+        let (text, styled) = overstrike(line);
+        let indent = text.chars().take_while(|c| c.is_whitespace()).count();
+        let trimmed = text.trim();
+        let allstyled = text
+            .chars()
+            .zip(styled.iter())
+            .all(|(c, s)| *s || c.is_whitespace());
+        let tag = match (indent, trimmed.is_empty()) {
+            (_, true) => Tag::Text,
+            // section and subsection headers
+            (0, _) if allstyled || isupper(trimmed) => {
+                section = trimmed.to_string();
+                Tag::Heading
+            }
+            (1..=3, _) if allstyled => Tag::Heading,
+            // options and other terms
+            (_, _) if styled.get(indent) == Some(&true) => Tag::Strong,
+            (_, _) => Tag::Indented,
+        };
+        pushref(&mut vec, &section, tag, text.trim_end());
+    }
+    vec
+}
+// strip overstriking and SGR escapes from a line, along with
+// which of the remaining characters were bold or underlined
+fn overstrike(line: &str) -> (String, Vec<bool>) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut text = String::new();
+    let mut styled = vec![];
+    // style set by SGR escapes
+    let mut sgr = false;
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == '\x1b' && chars.get(i + 1) == Some(&'[') {
+            let len = chars[i + 2..]
+                .iter()
+                .position(|c| c.is_ascii_alphabetic())
+                .unwrap_or(chars.len() - i - 2);
+            let params: String = chars[i + 2..i + 2 + len].iter().collect();
+            if chars.get(i + 2 + len) == Some(&'m') {
+                sgr = params
+                    .split(';')
+                    .any(|p| p == "1" || p == "4");
+            }
+            i += len + 3;
+            continue
+        }
+        if chars.get(i + 1) == Some(&'\x08') && i + 2 < chars.len() {
+            // "_\bx" shows x, "x\bx" shows x, "x\b_" shows x
+            let c = match (chars[i], chars[i + 2]) {
+                (a, '_') if a != '_' => a,
+                (_, b) => b,
+            };
+            i += 2;
+            while chars.get(i + 1) == Some(&'\x08') && i + 2 < chars.len() {
+                i += 2;
+            }
+            text.push(c);
+            styled.push(true);
+            i += 1;
+            continue
+        }
+        if chars[i] != '\x08' {
+            text.push(chars[i]);
+            styled.push(sgr);
+        }
+        i += 1;
+    }
+    (text, styled)
+}
+fn isupper(text: &str) -> bool {
+    text.chars().any(|c| c.is_alphabetic())
+        && text.chars().all(|c| !c.is_lowercase())
+}
+// roff source using the man macros
+pub fn parse_roff(lines: Vec<&str>) -> Vec<(Tag, String)> {
+    let mut roff = Roff::new();
+    for line in lines.iter() {
+        roff.line(line);
+    }
+    roff.flush();
+    roff.doc
+}
+// state of the formatter
+#[derive(Clone, Debug)]
+struct Roff {
+    doc: Vec<(Tag, String)>,
+    // words of the paragraph being filled
+    para: String,
+    // indent of the paragraph, and of where .RE returns to
+    indent: usize,
+    margins: Vec<usize>,
+    // indent of the text under a .TP term
+    hang: Option<usize>,
+    // the next line is a .TP term
+    term: bool,
+    // the next line is a .SH or .SS heading
+    heading: Option<usize>,
+    nofill: bool,
+    section: String,
+}
+impl Roff {
+    fn new() -> Self {
+        Self {
+            doc: vec![],
+            para: String::new(),
+            indent: INDENT,
+            margins: vec![],
+            hang: None,
+            term: false,
+            heading: None,
+            nofill: false,
+            section: String::new(),
+        }
+    }
+    fn line(&mut self, line: &str) {
+        let Some(request) = line.strip_prefix(['.', '\'']) else {
+            return self.text(line)
+        };
+        let request = request.trim_start();
+        if request.starts_with("\\\"") {
+            return
+        }
+        let (name, rest) = request
+            .split_once(char::is_whitespace)
+            .unwrap_or((request, ""));
+        let args = arguments(rest);
+        let joined = args.join(" ");
+        match name {
+            "TH" => {
+                let title = match (args.first(), args.get(1)) {
+                    (Some(n), Some(s)) => format!("{}({})", n, s),
+                    (Some(n), None) => n.to_string(),
+                    _ => return,
+                };
+                self.doc.push((Tag::Heading, title));
+            }
+            "SH" | "SS" => {
+                self.flush();
+                self.gap();
+                let indent = match name {
+                    "SH" => 0,
+                    _ => 3,
+                };
+                self.indent = INDENT;
+                self.margins.clear();
+                self.hang = None;
+                match joined.is_empty() {
+                    true => self.heading = Some(indent),
+                    false => self.pushheading(indent, &joined),
+                }
+            }
+            "PP" | "LP" | "P" => {
+                self.flush();
+                self.gap();
+                self.hang = None;
+            }
+            "TP" | "TQ" => {
+                self.flush();
+                if name == "TP" {
+                    self.gap();
+                }
+                self.hang = None;
+                self.term = true;
+            }
+            "IP" => {
+                self.flush();
+                self.gap();
+                self.hang = Some(self.indent + INDENT);
+                if let Some(tag) = args.first() {
+                    self.para = format!("{} ", tag);
+                }
+            }
+            "B" | "I" | "SM" | "SB" => self.text(&joined),
+            "BR" | "BI" | "IB" | "RB" | "RI" | "IR" => self.text(&args.concat()),
+            "br" => self.flush(),
+            "sp" => {
+                self.flush();
+                self.doc.push((Tag::Text, String::new()));
+            }
+            "nf" | "EX" => {
+                self.flush();
+                self.nofill = true;
+            }
+            "fi" | "EE" => {
+                self.flush();
+                self.nofill = false;
+            }
+            "RS" => {
+                self.flush();
+                self.margins.push(self.indent);
+                self.indent = self.hang.unwrap_or(self.indent) + INDENT;
+                self.hang = None;
+            }
+            "RE" => {
+                self.flush();
+                self.indent = self.margins.pop().unwrap_or(INDENT);
+                self.hang = None;
+            }
+            "UR" | "MT" => self.text(&joined),
+            _ => {}
+        }
+    }
+    fn text(&mut self, line: &str) {
+        let text = unescape(line);
+        if let Some(indent) = self.heading.take() {
+            return self.pushheading(indent, text.trim())
+        }
+        if self.nofill {
+            let indent = " ".repeat(self.indent);
+            self.doc.push((Tag::Pre, format!("{}{}", indent, text)));
+            return
+        }
+        if self.term {
+            self.term = false;
+            let indent = " ".repeat(self.indent);
+            self.doc.push((Tag::Strong, format!("{}{}", indent, text.trim())));
+            self.hang = Some(self.indent + INDENT);
+            return
+        }
+        // blank lines and leading spaces break the line
+        if text.trim().is_empty() {
+            self.flush();
+            self.gap();
+            return
+        }
+        if text.starts_with(' ') {
+            self.flush();
+        }
+        if !self.para.is_empty() && !self.para.ends_with(' ') {
+            self.para.push(' ');
+        }
+        self.para.push_str(text.trim());
+    }
+    fn pushheading(&mut self, indent: usize, text: &str) {
+        if indent == 0 {
+            self.section = text.to_uppercase();
+        }
+        let text = format!("{}{}", " ".repeat(indent), text);
+        self.doc.push((Tag::Heading, text));
+    }
+    // push the paragraph being filled
+    fn flush(&mut self) {
+        let para = std::mem::take(&mut self.para);
+        if para.trim().is_empty() {
+            return
+        }
+        let indent = " ".repeat(self.hang.unwrap_or(self.indent));
+        let text = format!("{}{}", indent, para.trim());
+        pushref(&mut self.doc, &self.section, Tag::Indented, &text);
+    }
+    // separate paragraphs with an empty line
+    fn gap(&mut self) {
+        match self.doc.last() {
+            None => {}
+            Some((Tag::Text, t)) if t.is_empty() => {}
+            _ => self.doc.push((Tag::Text, String::new())),
+        }
+    }
+}
+// split request arguments on spaces, keeping quoted arguments whole
+fn arguments(rest: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' => {}
+            '"' => {
+                let mut arg = String::new();
+                while let Some(c) = chars.next() {
+                    match (c, chars.peek()) {
+                        // "" inside quotes is a quote
+                        ('"', Some('"')) => {
+                            arg.push('"');
+                            chars.next();
+                        }
+                        ('"', _) => break,
+                        _ => arg.push(c),
+                    }
+                }
+                args.push(unescape(&arg));
+            }
+            _ => {
+                let mut arg = String::from(c);
+                while let Some(c) = chars.next_if(|c| *c != ' ' && *c != '\t') {
+                    arg.push(c);
+                }
+                args.push(unescape(&arg));
+            }
+        }
+    }
+    args
+}
+// replace escapes with the characters they stand for, dropping
+// font changes and the like
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue
+        }
+        let Some(e) = chars.next() else {
+            break
+        };
+        match e {
+            // the rest of the line is a comment
+            '"' => break,
+            '-' => out.push('-'),
+            'e' | '\\' => out.push('\\'),
+            ' ' | '~' | '0' => out.push(' '),
+            '\'' => out.push('\u{b4}'),
+            '`' => out.push('`'),
+            '&' | '%' | '|' | '^' | 'c' | ':' | ')' | '/' | ',' => {}
+            // font, size and register escapes are dropped
+            'f' | 'n' | 'F' => {
+                let _ = escapename(&mut chars);
+            }
+            's' => {
+                let _ = chars.next_if(|c| *c == '+' || *c == '-');
+                match chars.peek() {
+                    Some('[') | Some('(') => {
+                        let _ = escapename(&mut chars);
+                    }
+                    _ => {
+                        while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+                    }
+                }
+            }
+            '(' => {
+                let name: String = chars.by_ref().take(2).collect();
+                out.push_str(special(&name));
+            }
+            '[' => {
+                let name: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                out.push_str(special(&name));
+            }
+            '*' => {
+                let name = escapename(&mut chars);
+                out.push_str(special(&name));
+            }
+            'N' => {
+                let quoted: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|c| *c != '\'')
+                    .collect();
+                let c = quoted.parse().ok().and_then(char::from_u32);
+                if let Some(c) = c {
+                    out.push(c);
+                }
+            }
+            _ => out.push(e),
+        }
+    }
+    out
+}
+// the name after an escape, as x, (xx or [xxx]
+fn escapename(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    match chars.next() {
+        Some('(') => chars.by_ref().take(2).collect(),
+        Some('[') => chars.by_ref().take_while(|c| *c != ']').collect(),
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+// special characters and predefined strings
+fn special(name: &str) -> &'static str {
+    match name {
+        "em" => "\u{2014}",
+        "en" => "\u{2013}",
+        "bu" => "\u{2022}",
+        "aq" => "'",
+        "dq" => "\"",
+        "lq" => "\u{201c}",
+        "rq" => "\u{201d}",
+        "oq" => "\u{2018}",
+        "cq" => "\u{2019}",
+        "hy" | "mi" | "-" => "-",
+        "ti" => "~",
+        "ha" => "^",
+        "rs" => "\\",
+        "pl" => "+",
+        "mu" => "\u{d7}",
+        "de" => "\u{b0}",
+        "co" => "\u{a9}",
+        "R" | "rg" => "\u{ae}",
+        "Tm" | "tm" => "\u{2122}",
+        ">=" => "\u{2265}",
+        "<=" => "\u{2264}",
+        "->" => "\u{2192}",
+        "<-" => "\u{2190}",
+        _ => "",
+    }
+}
+// push a line, linking the references in SEE ALSO to their pages
+fn pushref(vec: &mut Vec<(Tag, String)>, section: &str, tag: Tag, text: &str) {
+    let links = match section {
+        "SEE ALSO" => references(text)
+            .into_iter()
+            .filter_map(|(name, sec)| {
+                let label = format!("{}({})", name, sec);
+                findpage(&name, &sec).map(|path| (path, label))
+            })
+            .collect(),
+        _ => vec![],
+    };
+    tag::pushline(vec, tag, text, links);
+}
+// every "name(section)" in the text
+pub fn references(text: &str) -> Vec<(String, String)> {
+    let mut refs = vec![];
+    let isname = |c: &char| c.is_alphanumeric() || "_.:+-".contains(*c);
+    for (open, _) in text.match_indices('(') {
+        let Some(close) = text[open..].find(')') else {
+            continue
+        };
+        let sec = &text[open + 1..open + close];
+        let name: String = text[..open]
+            .chars()
+            .rev()
+            .take_while(isname)
+            .collect::<Vec<char>>()
+            .into_iter()
+            .rev()
+            .collect();
+        let name = name.trim_start_matches(['.', '-']);
+        if !name.is_empty()
+            && sec.starts_with(|c: char| c.is_ascii_digit())
+            && sec.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            refs.push((name.to_string(), sec.to_string()));
+        }
+    }
+    refs
+}
+// path of a man page on MANPATH, or in the usual places
+pub fn findpage(name: &str, sec: &str) -> Option<String> {
+    let manpath = env::var("MANPATH").unwrap_or_default();
+    let mut dirs: Vec<&str> = manpath.split(':').filter(|d| !d.is_empty()).collect();
+    dirs.extend(["/usr/share/man", "/usr/local/share/man", "/usr/local/man"]);
+    let page = format!("{}.{}", name, sec);
+    for dir in dirs {
+        let subdir = Path::new(dir).join(format!("man{}", &sec[..1]));
+        let Ok(entries) = fs::read_dir(&subdir) else {
+            continue
+        };
+        for entry in entries.flatten() {
+            let file = entry.file_name();
+            let file = file.to_string_lossy();
+            if *file == page || file.starts_with(&format!("{}.", page)) {
+                return Some(entry.path().to_string_lossy().into_owned())
+            }
+        }
+    }
+    None
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    fn text(s: &str) -> String {
+        String::from(s)
+    }
+    // body text is filled and indented, the text under a .TP
+    // term hangs further in
+    #[test]
+    fn requests() {
+        let doc = parse_roff(vec![
+            ".TH LS 1",
+            ".SH NAME",
+            "ls \\- list",
+            ".SH OPTIONS",
+            ".TP",
+            ".B \\-a",
+            "show",
+            ".I all",
+            "entries",
+        ]);
+        assert_eq!(doc, [
+            (Tag::Heading, text("LS(1)")),
+            (Tag::Text, text("")),
+            (Tag::Heading, text("NAME")),
+            (Tag::Indented, text("       ls - list")),
+            (Tag::Text, text("")),
+            (Tag::Heading, text("OPTIONS")),
+            (Tag::Text, text("")),
+            (Tag::Strong, text("       -a")),
+            (Tag::Indented, text("              show all entries")),
+        ]);
+    }
+    #[test]
+    fn nofill() {
+        let doc = parse_roff(vec![".SH EXAMPLE", ".nf", "a   b", "  c", ".fi", "d", "e"]);
+        assert_eq!(doc, [
+            (Tag::Heading, text("EXAMPLE")),
+            (Tag::Pre, text("       a   b")),
+            (Tag::Pre, text("         c")),
+            (Tag::Indented, text("       d e")),
+        ]);
+    }
+    #[test]
+    fn escapes() {
+        assert_eq!(unescape("\\fBbold\\fR and \\f(CWit\\fP"), "bold and it");
+        assert_eq!(unescape("a\\(emb \\[lq]c\\[rq] \\e \\-x"), "a\u{2014}b \u{201c}c\u{201d} \\ -x");
+        assert_eq!(unescape("shown \\\" comment"), "shown ");
+    }
+    // "x\bx" is bold and "_\bx" underlined, both count as styled
+    #[test]
+    fn overstruck() {
+        let (plain, styled) = overstrike("l\x08ls\x08s _\x08a");
+        assert_eq!(plain, "ls a");
+        assert_eq!(styled, [true, true, false, true]);
+        let doc = parse_man("N\x08NA\x08AM\x08ME\x08E\n       l\x08ls\x08s - list _\x08f_\x08i_\x08l_\x08e_\x08s\n");
+        assert_eq!(doc, [
+            (Tag::Heading, text("NAME")),
+            (Tag::Strong, text("       ls - list files")),
+        ]);
+    }
+    #[test]
+    fn references_become_links() {
+        assert_eq!(references("ls(1), x11(7x) and f(a) (3)"), [
+            (text("ls"), text("1")),
+            (text("x11"), text("7x")),
+        ]);
+        let dir = TempDir::new("man");
+        fs::create_dir(dir.path.join("man1")).unwrap();
+        let page = dir.file("man1/pagertest.1.gz", b"");
+        // SAFETY: no other test reads or writes MANPATH
+        unsafe { env::set_var("MANPATH", &dir.path) };
+        // pages that can't be found are left as text
+        let doc = parse_roff(vec![".SH SEE ALSO", ".BR pagertest (1),", ".BR missing (1)"]);
+        assert_eq!(doc, [
+            (Tag::Heading, text("SEE ALSO")),
+            (Tag::Indented, text("       pagertest(1), missing(1)")),
+            (Tag::Link(page), text("pagertest(1)")),
+        ]);
+    }
+}
@@ -0,0 +1,341 @@
+// pager/src/markdown
+
+// Markdown is read line by line into the same tagged lines as the
+// markup in tag. A line has only one color, so emphasis is dropped,
+// and each inline link gets a link line of its own after its line.
+
+use crate::tag::{self, Tag};
+
+pub fn parse_markdown(lines: Vec<&str>) -> Vec<(Tag, String)> {
+    let mut vec = vec![];
+    // the fence that opened the current code block
+    let mut fence: Option<String> = None;
+    for line in lines.iter() {
+        let trimmed = line.trim();
+        if let Some(f) = &fence {
+            match trimmed.starts_with(f.as_str()) {
+                true => fence = None,
+                false => vec.push((Tag::Pre, line.to_string())),
+            }
+            continue
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let c = trimmed.chars().next().unwrap_or('`');
+            fence = Some(trimmed.chars().take_while(|x| *x == c).collect());
+            continue
+        }
+        // a setext heading is underlined on the line after it
+        if let Some((Tag::Text, text)) = vec.last()
+            && !text.is_empty()
+            && isunderline(trimmed)
+        {
+            let (_, text) = vec.pop().unwrap_or((Tag::Text, String::new()));
+            vec.push((Tag::Heading, text));
+            continue
+        }
+        parse_mdline(line, &mut vec);
+    }
+    tag::measure_tables(&mut vec);
+    vec
+}
+pub fn parse_mdline(line: &str, vec: &mut Vec<(Tag, String)>) {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        vec.push((Tag::Text, String::new()));
+        return
+    }
+    if trimmed.starts_with('#') {
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        let text = &trimmed[level..];
+        if level <= 6 && (text.is_empty() || text.starts_with(' ')) {
+            let text = text.trim().trim_end_matches('#').trim();
+            pushline(Tag::Heading, text, vec);
+            return
+        }
+    }
+    if isrule(trimmed) {
+        vec.push((Tag::Text, trimmed.to_string()));
+        return
+    }
+    // the row under the header row only sets alignment
+    if trimmed.starts_with('|') {
+        if !isdelimiter(trimmed) {
+            let inner = trimmed.strip_prefix('|').unwrap_or(trimmed);
+            let inner = inner.strip_suffix('|').unwrap_or(inner);
+            let cells = inner
+                .split('|')
+                .map(|c| inline(c.trim()).0)
+                .collect();
+            vec.push(tag::parse_row(cells));
+        }
+        return
+    }
+    if let Some(text) = trimmed.strip_prefix('>') {
+        let text = text.trim_start_matches(['>', ' ']);
+        pushline(Tag::Quote, text, vec);
+        return
+    }
+    if let Some((marker, text)) = item(line) {
+        pushline(Tag::Item(marker), text, vec);
+        return
+    }
+    pushline(Tag::Text, trimmed, vec);
+}
+// strip inline markup before pushing
+fn pushline(tag: Tag, text: &str, vec: &mut Vec<(Tag, String)>) {
+    let (plain, links) = inline(text);
+    tag::pushline(vec, tag, &plain, links);
+}
+// a list item's marker and text. unordered items get a bullet,
+// ordered items keep their number, nested items are indented
+fn item(line: &str) -> Option<(String, &str)> {
+    let trimmed = line.trim_start();
+    let depth = (line.len() - trimmed.len()) / 2;
+    let indent = "  ".repeat(depth);
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(text) = trimmed.strip_prefix(bullet) {
+            return Some((format!("{}\u{2022}", indent), text.trim()))
+        }
+    }
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    let rest = &trimmed[digits..];
+    if (1..10).contains(&digits) && (rest.starts_with(". ") || rest.starts_with(") ")) {
+        let marker = format!("{}{}", indent, &trimmed[..digits + 1]);
+        return Some((marker, rest[2..].trim()))
+    }
+    None
+}
+// "===" or "---" under a paragraph line
+fn isunderline(line: &str) -> bool {
+    !line.is_empty() && (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-'))
+}
+// three or more '-', '*' or '_', maybe with spaces between
+fn isrule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| *c != ' ').collect();
+    chars.len() >= 3
+        && ['-', '*', '_'].contains(&chars[0])
+        && chars.iter().all(|c| *c == chars[0])
+}
+// "|---|:---:|" and the like
+fn isdelimiter(line: &str) -> bool {
+    line.contains('-') && line.chars().all(|c| "|-: ".contains(c))
+}
+// remove inline markup, returning the plain text along with the
+// target and text of every link, image and autolink
+pub fn inline(text: &str) -> (String, Vec<(String, String)>) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut plain = String::new();
+    let mut links = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let prev = match i {
+            0 => None,
+            _ => Some(chars[i - 1]),
+        };
+        let next = chars.get(i + 1).copied();
+        match c {
+            // escaped punctuation is literal
+            '\\' if next.is_some_and(|n| n.is_ascii_punctuation()) => {
+                plain.push(chars[i + 1]);
+                i += 2;
+            }
+            // code spans are literal
+            '`' => {
+                let ticks = chars[i..].iter().take_while(|x| **x == '`').count();
+                let open = i + ticks;
+                match findticks(&chars, open, ticks) {
+                    Some(close) => {
+                        let code: String = chars[open..close].iter().collect();
+                        plain.push_str(code.trim());
+                        i = close + ticks;
+                    }
+                    None => {
+                        plain.extend(&chars[i..open]);
+                        i = open;
+                    }
+                }
+            }
+            '!' if next == Some('[') => {
+                i += 1;
+            }
+            '[' => match linkat(&chars, i) {
+                Some((label, target, end)) => {
+                    let (label, mut inner) = inline(&label);
+                    plain.push_str(&label);
+                    links.append(&mut inner);
+                    links.push((target, label));
+                    i = end;
+                }
+                None => {
+                    plain.push(c);
+                    i += 1;
+                }
+            },
+            '<' => {
+                let end = chars[i..].iter().position(|x| *x == '>');
+                let url: String = match end {
+                    Some(end) => chars[i + 1..i + end].iter().collect(),
+                    None => String::new(),
+                };
+                match (url.contains("://") || url.starts_with("mailto:"))
+                    && !url.contains(' ')
+                {
+                    true => {
+                        plain.push_str(&url);
+                        i += url.chars().count() + 2;
+                        links.push((url.clone(), url));
+                    }
+                    false => {
+                        plain.push(c);
+                        i += 1;
+                    }
+                }
+            }
+            // emphasis, unless it stands alone between spaces
+            '*' | '~' if prev.is_some_and(|p| !p.is_whitespace())
+                || next.is_some_and(|n| !n.is_whitespace()) =>
+            {
+                i += 1;
+            }
+            // underscores inside words are literal
+            '_' if !(prev.is_some_and(|p| p.is_alphanumeric())
+                && next.is_some_and(|n| n.is_alphanumeric())) =>
+            {
+                i += 1;
+            }
+            _ => {
+                plain.push(c);
+                i += 1;
+            }
+        }
+    }
+    (plain, links)
+}
+// index of the run of backticks that closes a code span
+fn findticks(chars: &[char], start: usize, ticks: usize) -> Option<usize> {
+    let mut i = start;
+    while i < chars.len() {
+        let run = chars[i..].iter().take_while(|x| **x == '`').count();
+        match run {
+            0 => i += 1,
+            _ if run == ticks => return Some(i),
+            _ => i += run,
+        }
+    }
+    None
+}
+// "[label](target)" starting at i, returns the label, the target
+// and the index just past the link
+fn linkat(chars: &[char], i: usize) -> Option<(String, String, usize)> {
+    let mut depth = 0;
+    let mut close = None;
+    for (j, c) in chars.iter().enumerate().skip(i) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    close = Some(j);
+                    break
+                }
+            }
+            _ => {}
+        }
+    }
+    let close = close?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None
+    }
+    let end = close + 2 + chars[close + 2..].iter().position(|c| *c == ')')?;
+    let label: String = chars[i + 1..close].iter().collect();
+    let dest: String = chars[close + 2..end].iter().collect();
+    // a title may follow the target
+    let target = dest.split_whitespace().next().unwrap_or("");
+    let target = target.trim_start_matches('<').trim_end_matches('>');
+    // files are followed, not their fragments
+    let target = match target.split_once('#') {
+        Some((file, _)) if !file.is_empty() => file,
+        _ => target,
+    };
+    Some((label, target.to_string(), end + 1))
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> String {
+        String::from(s)
+    }
+    #[test]
+    fn blocks() {
+        let doc = parse_markdown(vec![
+            "# Title #",
+            "Setext",
+            "===",
+            "",
+            "- item",
+            "  * nested",
+            "2. second",
+            "> quoted",
+            "***",
+            "```rust",
+            "let x = *y;",
+            "```",
+        ]);
+        assert_eq!(doc, [
+            (Tag::Heading, text("Title")),
+            (Tag::Heading, text("Setext")),
+            (Tag::Text, text("")),
+            (Tag::Item(text("\u{2022}")), text("item")),
+            (Tag::Item(text("  \u{2022}")), text("nested")),
+            (Tag::Item(text("2.")), text("second")),
+            (Tag::Quote, text("quoted")),
+            (Tag::Text, text("***")),
+            (Tag::Pre, text("let x = *y;")),
+        ]);
+    }
+    // the delimiter row is dropped, the rest are rows of one table
+    #[test]
+    fn tables() {
+        let doc = parse_markdown(vec!["| name | size |", "|:---|---:|", "| *a* | 1 |"]);
+        let rows: Vec<(Vec<String>, Vec<usize>)> = doc
+            .iter()
+            .map(|(t, _)| match t {
+                Tag::Row(r) => (r.cells.clone(), r.widths.clone()),
+                _ => panic!("{:?}", t),
+            })
+            .collect();
+        assert_eq!(rows, [
+            (vec![text("name"), text("size")], vec![4, 4]),
+            (vec![text("a"), text("1")], vec![4, 4]),
+        ]);
+    }
+    #[test]
+    fn inline_markup() {
+        assert_eq!(inline("some **bold** and _em_ ~~gone~~").0, "some bold and em gone");
+        assert_eq!(inline("`a*b_c` and ``x ` y``").0, "a*b_c and x ` y");
+        assert_eq!(inline("snake_case_name").0, "snake_case_name");
+        assert_eq!(inline("a * b and \\*c\\*").0, "a * b and *c*");
+    }
+    #[test]
+    fn inline_links() {
+        let (plain, links) = inline("see [the *docs*](guide.md#intro \"title\") or <https://x.org>");
+        assert_eq!(plain, "see the docs or https://x.org");
+        assert_eq!(links, [
+            (text("guide.md"), text("the docs")),
+            (text("https://x.org"), text("https://x.org")),
+        ]);
+        assert_eq!(inline("![logo](pic.png)"), (text("logo"), vec![(text("pic.png"), text("logo"))]));
+    }
+    // a line that is only a link is the link, others get link lines
+    #[test]
+    fn link_lines() {
+        let doc = parse_markdown(vec!["[home](index.md)", "go [home](index.md) now"]);
+        assert_eq!(doc, [
+            (Tag::Link(text("index.md")), text("home")),
+            (Tag::Text, text("go home now")),
+            (Tag::Link(text("index.md")), text("home")),
+        ]);
+    }
+}
@@ -0,0 +1,201 @@
+// pager/src/screen
+
+// Views draw into a screen buffer rather than straight to the
+// terminal. The buffer keeps the frame that was last sent, and only
+// the cells that changed since then are sent again. A wide character
+// takes its cell and the one after, which is left as a continuation.
+
+use crate::backend::Backend;
+use crossterm::style::{Color, Colors};
+use std::io;
+use unicode_width::UnicodeWidthChar;
+
+// a character and the colors it's drawn in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cell {
+    pub ch: char,
+    pub colors: Colors,
+}
+impl Cell {
+    // the second half of a wide character, drawn by the cell before it
+    pub const CONTINUATION: char = '\0';
+
+    pub fn blank() -> Self {
+        Self {ch: ' ', colors: Colors::new(Color::Reset, Color::Reset)}
+    }
+    pub fn iscontinuation(&self) -> bool {
+        self.ch == Self::CONTINUATION
+    }
+}
+#[derive(Clone, Debug)]
+pub struct Screen {
+    pub w: u16,
+    pub h: u16,
+    // the frame being drawn
+    cells: Vec<Cell>,
+    // the frame on the terminal, empty when it isn't known
+    last: Vec<Cell>,
+    // where print draws, and in what colors
+    x: u16,
+    y: u16,
+    colors: Colors,
+    // where the terminal cursor is left, hidden if None
+    cursor: Option<(u16, u16)>,
+}
+impl Screen {
+    pub fn new(w: u16, h: u16) -> Self {
+        Self {
+            w,
+            h,
+            cells: vec![Cell::blank(); usize::from(w) * usize::from(h)],
+            last: vec![],
+            x: 0,
+            y: 0,
+            colors: Cell::blank().colors,
+            cursor: Some((0, 0)),
+        }
+    }
+    // the terminal is redrawn in full after a resize
+    pub fn resize(&mut self, w: u16, h: u16) {
+        *self = Self::new(w, h);
+    }
+    // start a new frame
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::blank());
+        self.moveto(0, 0);
+        self.colors = Cell::blank().colors;
+    }
+    pub fn moveto(&mut self, x: u16, y: u16) {
+        self.x = x;
+        self.y = y;
+    }
+    pub fn setcolors(&mut self, colors: Colors) {
+        self.colors = colors;
+    }
+    pub fn setcursor(&mut self, x: u16, y: u16) {
+        self.cursor = Some((x, y));
+    }
+    pub fn hidecursor(&mut self) {
+        self.cursor = None;
+    }
+    // one cell per column, whatever runs off the edge is lost. wide
+    // characters that don't fit at the edge become a space. combining
+    // marks have no cell of their own and are dropped, control
+    // characters should have been made visible already
+    pub fn print(&mut self, text: &str) {
+        for ch in text.chars() {
+            let ch = match ch.is_control() {
+                true => '\u{fffd}',
+                false => ch,
+            };
+            match ch.width().unwrap_or(0) {
+                0 => continue,
+                1 => self.put(self.x, ch),
+                _ if self.x.saturating_add(1) < self.w => {
+                    self.put(self.x, ch);
+                    self.put(self.x + 1, Cell::CONTINUATION);
+                    self.x += 1;
+                }
+                _ => self.put(self.x, ' '),
+            }
+            self.x = self.x.saturating_add(1);
+        }
+        self.cursor = Some((self.x, self.y));
+    }
+    // a wide character partly drawn over is blanked, so no half of
+    // one is left behind
+    fn put(&mut self, x: u16, ch: char) {
+        if x >= self.w || self.y >= self.h {
+            return
+        }
+        let row = usize::from(self.y) * usize::from(self.w);
+        let i = row + usize::from(x);
+        let end = row + usize::from(self.w);
+        if self.cells[i].iscontinuation() && i > row {
+            self.cells[i - 1].ch = ' ';
+        }
+        if ch != Cell::CONTINUATION && i + 1 < end && self.cells[i + 1].iscontinuation() {
+            self.cells[i + 1].ch = ' ';
+        }
+        self.cells[i] = Cell {ch, colors: self.colors};
+    }
+    // send the cells that changed since the last frame
+    pub fn flush(&mut self, backend: &mut dyn Backend) -> io::Result<()> {
+        let full = self.last.len() != self.cells.len();
+        backend.begin(self.w, self.h, full)?;
+        if full {
+            self.last = vec![Cell::blank(); self.cells.len()];
+        }
+        let w = usize::from(self.w);
+        for (i, cell) in self.cells.iter().enumerate() {
+            if *cell != self.last[i] {
+                backend.put((i % w) as u16, (i / w) as u16, *cell)?;
+            }
+        }
+        backend.end(self.cursor)?;
+        self.last.clone_from(&self.cells);
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{Grid, Terminal};
+
+    fn frame(screen: &mut Screen) -> Grid {
+        let mut grid = Grid::new();
+        screen.flush(&mut grid).unwrap();
+        grid
+    }
+    #[test]
+    fn wide_characters_take_two_cells() {
+        let mut screen = Screen::new(8, 1);
+        screen.print("a日b");
+        let grid = frame(&mut screen);
+        assert_eq!(grid.rows(), ["a日b"]);
+        assert_eq!(grid.cell(1, 0).ch, '日');
+        assert!(grid.cell(2, 0).iscontinuation());
+        assert_eq!(grid.cell(3, 0).ch, 'b');
+        assert_eq!(grid.cursor, Some((4, 0)));
+    }
+    #[test]
+    fn wide_characters_at_the_edge() {
+        let mut screen = Screen::new(4, 1);
+        screen.print("abc日");
+        assert_eq!(frame(&mut screen).rows(), ["abc"]);
+    }
+    // drawing over either half of a wide character blanks the other
+    #[test]
+    fn halves_are_not_left_behind() {
+        let mut screen = Screen::new(6, 1);
+        screen.print("日本");
+        screen.moveto(1, 0);
+        screen.print("x");
+        screen.moveto(2, 0);
+        screen.print("y");
+        let grid = frame(&mut screen);
+        assert_eq!(grid.rows(), [" xy"]);
+        assert!((0..6).all(|x| !grid.cell(x, 0).iscontinuation()));
+    }
+    #[test]
+    fn controls_and_combining_marks() {
+        let mut screen = Screen::new(8, 1);
+        screen.print("a\x1bb\u{301}c");
+        assert_eq!(frame(&mut screen).rows(), ["a\u{fffd}bc"]);
+    }
+    // the terminal moves two columns after a wide character, so the
+    // next cell needs no cursor movement
+    #[test]
+    fn terminal_follows_wide_characters() {
+        let mut screen = Screen::new(4, 1);
+        screen.print("日a");
+        let mut out = vec![];
+        let mut terminal = Terminal::new(&mut out);
+        screen.flush(&mut terminal).unwrap();
+        let text = String::from_utf8_lossy(&out).into_owned();
+        let moves = text.matches('H').count();
+        assert!(text.contains("日a"), "{:?}", text);
+        // one to start the frame, one for the cursor at the end
+        assert_eq!(moves, 2, "{:?}", text);
+    }
+}
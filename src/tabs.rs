@@ -1,80 +1,144 @@
 // pager/src/tabs
 
-use crate::util::{Rect};
+use crate::util::{self, Rect};
 use crate::widget::{Selector, Dialog, InputType, DialogMsg};
 use crate::util::{ViewMsg};
-use crate::tag::{self, Tag};
-use crossterm::{QueueableCommand, cursor, terminal};
+use crate::compress::Compression;
+use crate::config::{Options, SearchOptions, Start};
+use crate::keys::Command;
+use crate::doc::{Cache, Document};
+use crate::format::{DocumentFormat, Registry};
+use crate::screen::Screen;
+use crate::tag::Tag;
+use crate::theme::Theme;
 use crossterm::event::{KeyCode};
-use crossterm::style::{self, Colors, Color};
-use std::{fs};
-use std::io::{self, Stdout};
+use std::io;
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub struct TabMgr {
     rect: Rect,
     tabs: Vec<Tab>,
+    // formats of the documents tabs are opened on
+    formats: Registry,
+    options: Options,
+    // documents open in tabs
+    cache: Cache,
     // index of current tab
     curindex: usize,
+    // colors of the banner and pages
+    theme: Theme,
     // meta data to display at all times
     bannerstr: String,
-    bannerstrcolor: Colors,
     // separate banner from page
     bannerline: String,
-    bannerlinecolor: Colors,
 }
 impl TabMgr {
-    pub fn new(rect: &Rect, path: &str) -> Self {
-        let rect = Rect::new(rect.x, rect.y + 2, rect.w, rect.h - 1);
-        Self {
+    pub fn new(
+        rect: &Rect,
+        paths: &[String],
+        formats: Registry,
+        options: Options,
+    ) -> io::Result<Self> {
+        let rect = Rect::new(rect.x, rect.y + 2, rect.w, rect.h.saturating_sub(2));
+        let mut cache = Cache::new(options.encoding);
+        let mut tabs = vec![];
+        // a tab for each file, all starting at the same place
+        for path in paths.iter() {
+            let format = options.formatfor(&formats, path);
+            let doc = cache
+                .open(path, &format)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+            let mut tab = Tab::new(&rect, path, format, doc, &options);
+            tab.start(&options.start, &options.search);
+            tabs.push(tab);
+        }
+        if tabs.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no files to show"))
+        }
+        Ok(Self {
             rect: rect.clone(),
-            tabs: vec![Tab::new(&rect, &path)],
+            bannerstr: Self::bannerstr(&options.banner, 0, tabs.len(), &tabs[0]),
+            tabs,
+            formats,
+            theme: options.palette(),
+            options,
+            cache,
             curindex: 0,
-            bannerstr: Self::bannerstr(0, 1, path),
             bannerline: Self::bannerline(rect.w),
-            bannerstrcolor: Colors::new(
-                Color::Rgb {r: 180, g: 180, b: 180},
-                Color::Rgb {r: 0, g: 0, b: 0}),
-            bannerlinecolor: Colors::new(
-                Color::Rgb {r: 180, g: 180, b: 180},
-                Color::Rgb {r: 0, g: 0, b: 0}),
-        }
+        })
     }
     // adjust length of banner line, resize all tabs
     pub fn resize(&mut self, rect: &Rect) {
-        self.rect = Rect::new(rect.x, rect.y + 2, rect.w, rect.h - 1);
+        self.rect = Rect::new(rect.x, rect.y + 2, rect.w, rect.h.saturating_sub(2));
         self.bannerline = Self::bannerline(rect.w);
         for d in self.tabs.iter_mut() {
             d.resize(&self.rect);
         }
     }
     // display banner and page
-    pub fn view(&self, mut stdout: &Stdout) -> io::Result<()> {
-        stdout
-            .queue(terminal::Clear(terminal::ClearType::All))?
-            .queue(cursor::MoveTo(0, 0))?
-            .queue(style::SetColors(self.bannerstrcolor))?
-            .queue(style::Print(self.bannerstr.as_str()))?
-            .queue(cursor::MoveTo(0, 1))?
-            .queue(style::SetColors(self.bannerlinecolor))?
-            .queue(style::Print(&self.bannerline))?;
-        self.tabs[self.curindex].view(stdout)
+    pub fn view(&self, screen: &mut Screen) {
+        screen.moveto(0, 0);
+        screen.setcolors(self.theme.banner);
+        screen.print(&self.bannerstr);
+        screen.moveto(0, 1);
+        screen.setcolors(self.theme.bannerline);
+        screen.print(&self.bannerline);
+        self.tabs[self.curindex].view(screen, &self.theme)
     }
-    // send keycode to current tab and process response
+    pub fn notify(&mut self, message: &str) {
+        self.tabs[self.curindex].notify(message);
+    }
+    // whether keys go to a dialog rather than being commands
+    pub fn indialog(&self) -> bool {
+        self.tabs[self.curindex].indialog()
+    }
+    // send keycode to the current tab's dialog and process response
     pub fn update(&mut self, keycode: &KeyCode) -> bool {
-        match self.tabs[self.curindex].update(keycode) {
+        let msg = self.tabs[self.curindex].update(keycode);
+        self.process(msg)
+    }
+    // send command, and the count typed before it, to current tab
+    // and process response
+    pub fn command(&mut self, command: Command, count: Option<usize>) -> bool {
+        let msg = self.tabs[self.curindex].command(command, count);
+        self.process(msg)
+    }
+    fn process(&mut self, msg: Option<TabMsg>) -> bool {
+        match msg {
             Some(msg) => {
                 match msg {
                     TabMsg::Msg(ViewMsg::Go(p)) => {
-                        self.tabs.push(Tab::new(&self.rect, &p));
-                        self.curindex = self.tabs.len() - 1;
+                        let format = self.formats.detect(&p);
+                        match self.cache.open(&p, &format) {
+                            Ok(doc) => {
+                                let tab = Tab::new(&self.rect, &p, format, doc, &self.options);
+                                self.tabs.push(tab);
+                                self.curindex = self.tabs.len() - 1;
+                            }
+                            Err(e) => self.tabs[self.curindex].notify(
+                                &format!("can't open {}: {}", p, e)),
+                        }
                     }
-                    TabMsg::DeleteMe => {
-                        if self.tabs.len() > 1 {
-                            self.tabs.remove(self.curindex);
-                            self.curindex = self.tabs.len() - 1;
+                    // every tab on the document gets the new version
+                    TabMsg::Reload => {
+                        let tab = &self.tabs[self.curindex];
+                        let (path, format) = (tab.path.clone(), tab.format.clone());
+                        let key = Cache::key(&path, &format);
+                        match self.cache.reload(&path, &format) {
+                            Ok(doc) => for t in self.tabs.iter_mut() {
+                                if Cache::key(&t.path, &t.format) == key {
+                                    t.setdocument(doc.clone());
+                                }
+                            }
+                            Err(e) => self.tabs[self.curindex].notify(
+                                &format!("can't reload {}: {}", path, e)),
                         }
                     }
+                    TabMsg::DeleteMe if self.tabs.len() > 1 => {
+                        self.tabs.remove(self.curindex);
+                        self.curindex = self.tabs.len() - 1;
+                    }
                     TabMsg::CycleLeft => {
                         match self.curindex == 0 {
                             true => self.curindex = self.tabs.len() - 1,
@@ -90,16 +154,27 @@ impl TabMgr {
                     _ => {},
                 }
                 let len = self.tabs.len();
-                let path = &self.tabs[self.curindex].path;
-                self.bannerstr = Self::bannerstr(self.curindex, len, path);
+                let tab = &self.tabs[self.curindex];
+                self.bannerstr = Self::bannerstr(&self.options.banner, self.curindex, len, tab);
                 self.bannerline = Self::bannerline(self.rect.w);
                 true
             }
             None => false,
         }
     }
-    fn bannerstr(curindex: usize, totaltab: usize, path: &str) -> String {
-        format!("{}/{}: {}", curindex + 1, totaltab, path)
+    // fill in the banner format. compressed files show their
+    // compression and compressed size
+    fn bannerstr(banner: &str, curindex: usize, totaltab: usize, tab: &Tab) -> String {
+        let compression = match &tab.compressed {
+            Some((c, size)) => format!(" ({}, {})", c.name(), util::humansize(*size)),
+            None => String::new(),
+        };
+        banner
+            .replace("{index}", &(curindex + 1).to_string())
+            .replace("{count}", &totaltab.to_string())
+            .replace("{path}", &tab.path)
+            .replace("{format}", tab.format.names().first().copied().unwrap_or_default())
+            .replace("{compression}", &compression)
     }
     fn bannerline(w: u16) -> String {
         String::from("-").repeat(usize::from(w))
@@ -110,6 +185,7 @@ impl TabMgr {
 pub enum Action {
     None,
     GoTo,
+    Line,
     DeleteMe,
     Go(String),
 }
@@ -119,39 +195,77 @@ pub enum TabMsg {
     CycleRight,
     DeleteMe,
     Go(String),
+    Reload,
     Msg(ViewMsg),
 }
 #[derive(Clone, Debug)]
 pub struct Tab {
     rect: Rect,
     pub path: String,
+    format: Rc<dyn DocumentFormat>,
+    // how the file was compressed, and its size on disk
+    compressed: Option<(Compression, u64)>,
     dlgstack: Vec<Dialog<Action>>,
     page: Selector<Tag>,
 }
 impl Tab {
-    pub fn new(rect: &Rect, path: &str) -> Self {
-        let src = fs::read_to_string(&path).unwrap();
-        let text = tag::parse_doc(src.lines().collect());
+    pub fn new(
+        rect: &Rect,
+        path: &str,
+        format: Rc<dyn DocumentFormat>,
+        doc: Document,
+        options: &Options,
+    ) -> Self {
         Self {
+            compressed: doc.compressed,
             rect: rect.clone(),
             path: String::from(path),
+            format,
             dlgstack: vec![],
-            page: Selector::new(rect, text, true),
+            page: Selector::new(
+                rect,
+                doc.source,
+                options.wrap,
+                options.numbers,
+                options.cursor,
+                options.tabstop,
+            ),
         }
     }
     // show dialog if there's a dialog, otherwise show page
-    pub fn view(&self, stdout: &Stdout) -> io::Result<()> {
+    pub fn view(&self, screen: &mut Screen, theme: &Theme) {
         match self.dlgstack.last() {
-            Some(d) => d.view(stdout),
-            _ => self.page.view(stdout),
+            Some(d) => d.view(screen),
+            _ => self.page.view(screen, theme),
+        }
+    }
+    // move to where the document was asked to be opened
+    pub fn start(&mut self, start: &Start, search: &SearchOptions) {
+        match start {
+            Start::Top => {}
+            Start::Line(n) => self.page.show(n.saturating_sub(1), 0),
+            Start::Search(pattern) => match self.page.find(pattern, 0, search) {
+                Some(line) => self.page.show(line, 0),
+                None => self.notify(&format!("pattern not found: {}", pattern)),
+            },
         }
     }
+    // tell the user something, until they press Enter
+    pub fn notify(&mut self, message: &str) {
+        let dialog = Dialog::new(&self.rect, Action::None, InputType::None, message);
+        self.dlgstack.push(dialog);
+    }
+    // show a new version of the document
+    pub fn setdocument(&mut self, doc: Document) {
+        self.compressed = doc.compressed;
+        self.page.setsource(doc.source);
+    }
     // resize page and all dialogs
     pub fn resize(&mut self, rect: &Rect) {
         self.rect = rect.clone();
-        self.page.resize(&rect);
+        self.page.resize(rect);
         for d in self.dlgstack.iter_mut() {
-            d.resize(&rect);
+            d.resize(rect);
         }
     }
     pub fn update(&mut self, keycode: &KeyCode) -> Option<TabMsg> {
@@ -171,6 +285,15 @@ impl Tab {
                         (Action::GoTo, InputType::Input(v)) => {
                             Some(TabMsg::Msg(ViewMsg::Go(v.clone())))
                         }
+                        (Action::Line, InputType::Input(v)) => {
+                            let v = v.clone();
+                            self.dlgstack.pop();
+                            match v.trim().parse::<usize>() {
+                                Ok(n) if n > 0 => self.page.show(n - 1, 0),
+                                _ => self.notify(&format!("not a line number: {}", v)),
+                            }
+                            return Some(TabMsg::Msg(ViewMsg::None))
+                        }
                         (Action::DeleteMe, InputType::Choose((c, _))) => {
                             match c {
                                 'y' => 
@@ -195,9 +318,17 @@ impl Tab {
                _ => return None
             }
         }
-        // there is no dialog, process keycode here
-        match keycode {
-            KeyCode::Char('v') => {
+        None
+    }
+    pub fn indialog(&self) -> bool {
+        !self.dlgstack.is_empty()
+    }
+    // a count repeats moves, and makes top and bottom go to that line
+    pub fn command(&mut self, command: Command, count: Option<usize>) -> Option<TabMsg> {
+        let n = count.unwrap_or(1);
+        let page = usize::from(self.rect.h);
+        match command {
+            Command::CloseTab => {
                 let dialog = Dialog::new(
                     &self.rect,
                     Action::DeleteMe,
@@ -208,7 +339,7 @@ impl Tab {
                 self.dlgstack.push(dialog);
                 Some(TabMsg::Msg(ViewMsg::None))
             }
-            KeyCode::Char('p') => {
+            Command::OpenPath => {
                 let dialog = Dialog::new(
                     &self.rect,
                     Action::GoTo,
@@ -217,24 +348,102 @@ impl Tab {
                 self.dlgstack.push(dialog);
                 Some(TabMsg::Msg(ViewMsg::None))
             }
-            KeyCode::Char('i') => {
-                self.page.cursor.movedown(1);
+            Command::Down => {
+                self.page.movedown(n);
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::Up => {
+                self.page.moveup(n);
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::PageDown => {
+                self.page.pagedown(page.saturating_mul(n));
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::PageUp => {
+                self.page.pageup(page.saturating_mul(n));
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::HalfPageDown => {
+                self.page.pagedown(std::cmp::max(page / 2, 1).saturating_mul(n));
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::HalfPageUp => {
+                self.page.pageup(std::cmp::max(page / 2, 1).saturating_mul(n));
                 Some(TabMsg::Msg(ViewMsg::None))
             }
-            KeyCode::Char('o') => {
-                self.page.cursor.moveup(1);
+            Command::Top | Command::Bottom | Command::GoToLine if count.is_some() => {
+                self.page.show(n.saturating_sub(1), 0);
                 Some(TabMsg::Msg(ViewMsg::None))
             }
-            KeyCode::Char('e') => {
+            Command::Top => {
+                self.page.movetop();
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::Bottom => {
+                self.page.movebottom();
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::ScrollDown => {
+                self.page.scrolldown(n);
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::ScrollUp => {
+                self.page.scrollup(n);
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::Center => {
+                self.page.center();
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::LineToTop => {
+                self.page.linetotop();
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::LineToBottom => {
+                self.page.linetobottom();
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            // half a screen, or as many columns as the count
+            Command::ScrollLeft => {
+                self.page.scrollleft(count.unwrap_or(usize::from(self.rect.w / 2)));
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::ScrollRight => {
+                self.page.scrollright(count.unwrap_or(usize::from(self.rect.w / 2)));
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::ToggleWrap => {
+                self.page.togglewrap();
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::ToggleNumbers => {
+                self.page.setnumbers(self.page.numbers().next());
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::GoToLine => {
+                let dialog = Dialog::new(
+                    &self.rect,
+                    Action::Line,
+                    InputType::Input(String::from("")),
+                    "go to line: ");
+                self.dlgstack.push(dialog);
+                Some(TabMsg::Msg(ViewMsg::None))
+            }
+            Command::Reload => {
+                Some(TabMsg::Reload)
+            }
+            Command::PreviousTab => {
                 Some(TabMsg::CycleLeft)
             }
-            KeyCode::Char('n') => {
+            Command::NextTab => {
                 Some(TabMsg::CycleRight)
             }
             // make a dialog
-            KeyCode::Enter => {
-                let dialog = match self.page.selectundercursor() {
-                    Tag::Text => Dialog::new(
+            Command::Select => {
+                let dialog = match self.page.selectundercursor()? {
+                    Tag::Text | Tag::Quote | Tag::Item(_) | Tag::Pre | Tag::Strong | Tag::Indented
+                        => Dialog::new(
                         &self.rect,
                         Action::None,
                         InputType::None,
@@ -244,6 +453,11 @@ impl Tab {
                         Action::None,
                         InputType::None,
                         "You've selected a heading "),
+                    Tag::Row(_) => Dialog::new(
+                        &self.rect,
+                        Action::None,
+                        InputType::None,
+                        "You've selected a table row. "),
                     Tag::Link(l) => Dialog::new(
                         &self.rect,
                         Action::Go(l.to_string()),
@@ -1,40 +1,201 @@
 // pager/src/tag
 
-use crossterm::style::{Color, Colors};
+use crate::util::{self, Layout};
+use crate::theme::Theme;
+use crossterm::style::Colors;
 
 pub trait GetColors {
-    fn getcolors(&self) -> Colors;
+    fn getcolors(&self, theme: &Theme) -> Colors;
 }
 impl GetColors for Tag {
-    fn getcolors(&self) -> Colors {
+    fn getcolors(&self, theme: &Theme) -> Colors {
         match self {
-            Tag::Heading => Colors::new(
-                Color::Rgb {r: 225, g: 105, b: 105},
-                Color::Rgb {r: 0, g: 0, b: 0},
-            ),
-            Tag::Text => Colors::new(
-                Color::Rgb {r: 180, g: 180, b: 180},
-                Color::Rgb {r: 0, g: 0, b: 0},
-            ),
-            Tag::Link(_) => Colors::new(
-                Color::Rgb {r: 105, g: 180, b: 225},
-                Color::Rgb {r: 0, g: 0, b: 0},
-            ),
-        } 
+            Tag::Heading => theme.heading,
+            Tag::Text | Tag::Indented => theme.text,
+            Tag::Link(_) => theme.link,
+            Tag::Quote => theme.quote,
+            Tag::Item(_) => theme.item,
+            Tag::Strong => theme.strong,
+            Tag::Pre => theme.pre,
+            Tag::Row(row) => match row.header {
+                true => theme.header,
+                false => theme.text,
+            },
+        }
+    }
+}
+// table rows are laid out in columns, list items and quotes are
+// indented, preformatted text is never wrapped. the indented text
+// of man pages wraps under its own indentation
+impl Layout for Tag {
+    fn wrap(&self, text: &str, w: u16) -> Vec<String> {
+        match self {
+            Tag::Indented | Tag::Strong => {
+                let trimmed = text.trim_start();
+                let lead = &text[..text.len() - trimmed.len()];
+                match util::width(lead) < usize::from(w / 2) {
+                    true => indent(lead, lead, trimmed, w, true),
+                    false => util::wrap(text, w),
+                }
+            }
+            Tag::Row(row) => row.layout(text, w, true),
+            Tag::Item(marker) => {
+                let first = format!("{} ", marker);
+                let rest = " ".repeat(util::width(&first));
+                indent(&first, &rest, text, w, true)
+            }
+            Tag::Quote => indent("> ", "> ", text, w, true),
+            Tag::Pre => self.cut(text, w),
+            _ => util::wrap(text, w),
+        }
+    }
+    fn cut(&self, text: &str, w: u16) -> Vec<String> {
+        match self {
+            Tag::Row(row) => row.layout(text, w, false),
+            Tag::Item(marker) => {
+                let first = format!("{} ", marker);
+                let rest = " ".repeat(util::width(&first));
+                indent(&first, &rest, text, w, false)
+            }
+            Tag::Quote => indent("> ", "> ", text, w, false),
+            _ => vec![util::cut(text, w)],
+        }
     }
 }
+// lay out text in the width left over after a prefix. the first
+// line gets one prefix, the lines it wraps onto get another
+fn indent(first: &str, rest: &str, text: &str, w: u16, wrap: bool) -> Vec<String> {
+    let prefix = u16::try_from(util::width(first)).unwrap_or(u16::MAX);
+    let width = w.saturating_sub(prefix);
+    let lines = match (wrap, width) {
+        (_, 0) => return vec![util::cut(text, w)],
+        (true, _) => util::wrap(text, width),
+        (false, _) => vec![util::cut(text, width)],
+    };
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, l)| match i {
+            0 => format!("{}{}", first, l),
+            _ => format!("{}{}", rest, l),
+        })
+        .collect()
+}
 #[derive(Clone, PartialEq, Debug)]
 pub enum Tag {
     Heading,
-    Text, 
+    Text,
     Link(String),
-} 
+    Row(Row),
+    Quote,
+    // list item with its bullet or number
+    Item(String),
+    Pre,
+    // bold text standing on its own, like the options of a man page
+    Strong,
+    // text of a man page, set in from the margin
+    Indented,
+}
+// goes between the cells of a row
+const SEPARATOR: &str = " | ";
+// one row of a table. widths are the natural widths of the
+// columns of the whole table, so every row lines up
+#[derive(Clone, PartialEq, Debug)]
+pub struct Row {
+    pub cells: Vec<String>,
+    pub widths: Vec<usize>,
+    pub header: bool,
+}
+impl Row {
+    // columns shrink to fit in w, returns None if even
+    // one character per column won't fit
+    fn fitwidths(&self, w: u16) -> Option<Vec<usize>> {
+        let columns = self.widths.len();
+        let separators = SEPARATOR.len() * columns.checked_sub(1)?;
+        let mut left = usize::from(w).checked_sub(separators)?;
+        if left < columns {
+            return None
+        }
+        if self.widths.iter().sum::<usize>() <= left {
+            return Some(self.widths.clone())
+        }
+        // narrow columns keep their width, wide columns share the rest
+        let mut fitted = vec![0; columns];
+        let mut open: Vec<usize> = (0..columns).collect();
+        loop {
+            let share = left / open.len();
+            let (narrow, wide): (Vec<usize>, Vec<usize>) = open
+                .iter()
+                .partition(|i| self.widths[**i] <= share);
+            if narrow.is_empty() {
+                let extra = left % wide.len();
+                for (k, i) in wide.iter().enumerate() {
+                    fitted[*i] = share + usize::from(k < extra);
+                }
+                return Some(fitted)
+            }
+            for i in narrow {
+                fitted[i] = self.widths[i];
+                left -= self.widths[i];
+            }
+            open = wide;
+        }
+    }
+    // lay out cells side by side, wrapping or cutting each cell to
+    // its column. the header row is underlined
+    pub fn layout(&self, text: &str, w: u16, wrap: bool) -> Vec<String> {
+        let Some(widths) = self.fitwidths(w) else {
+            return match wrap {
+                true => util::wrap(text, w),
+                false => vec![util::cut(text, w)],
+            }
+        };
+        let cells: Vec<Vec<String>> = self.cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| fitcell(cell, *width, wrap))
+            .collect();
+        let height = cells.iter().map(|c| c.len()).max().unwrap_or(0).max(1);
+        let mut lines = vec![];
+        for j in 0..height {
+            let line: Vec<String> = cells
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| {
+                    let part = cell.get(j).map(|s| s.as_str()).unwrap_or("");
+                    util::pad(part, *width)
+                })
+                .collect();
+            lines.push(line.join(SEPARATOR).trim_end().to_string());
+        }
+        if self.header {
+            let rule: Vec<String> = widths
+                .iter()
+                .map(|width| "-".repeat(*width))
+                .collect();
+            lines.push(rule.join("-+-"));
+        }
+        lines
+    }
+}
+// wrap or cut one cell to the width of its column
+fn fitcell(cell: &str, width: usize, wrap: bool) -> Vec<String> {
+    if util::width(cell) <= width {
+        return vec![cell.to_string()]
+    }
+    let w = u16::try_from(width).unwrap_or(u16::MAX);
+    match wrap {
+        true => util::wrap(cell, w),
+        false => vec![util::cut(cell, w)],
+    }
+}
 pub fn parse_doc(lines: Vec<&str>) -> Vec<(Tag, String)> {
     let mut vec = vec![];
     for line in lines.iter() {
         let formatted = parse_line(line);
         vec.push(formatted);
     }
+    measure_tables(&mut vec);
     vec
 }
 pub fn parse_line(line: &str) -> (Tag, String) {
@@ -44,13 +205,215 @@ pub fn parse_line(line: &str) -> (Tag, String) {
             match text.split_once(' ') {
                 Some((link, txt)) =>
                     return (Tag::Link(link.to_string()), txt.to_string()),
-                None => 
+                None =>
                     return (Tag::Link(text.to_string()), text.to_string()),
             }
         }
         if symbol == ".h" {
             return (Tag::Heading, text.to_string())
         }
+        // cells are separated by '|'
+        if symbol == ".t" {
+            let cells: Vec<String> = text
+                .split('|')
+                .map(|c| c.trim().to_string())
+                .collect();
+            return parse_row(cells)
+        }
+    }
+    (Tag::Text, line.to_string())
+}
+// push a line and then a link line for each of the links in it,
+// given as (target, text). a line that is nothing but a link
+// becomes that link
+pub fn pushline(
+    vec: &mut Vec<(Tag, String)>,
+    tag: Tag,
+    text: &str,
+    links: Vec<(String, String)>,
+) {
+    if let [(target, label)] = links.as_slice()
+        && tag != Tag::Heading
+        && label == text.trim()
+    {
+        vec.push((Tag::Link(target.clone()), label.clone()));
+        return
+    }
+    vec.push((tag, text.to_string()));
+    for (target, label) in links {
+        vec.push((Tag::Link(target), label));
+    }
+}
+// a table row, to be measured along with the rest of its table
+pub fn parse_row(cells: Vec<String>) -> (Tag, String) {
+    let text = cells.join(SEPARATOR);
+    let row = Row {cells, widths: vec![], header: false};
+    (Tag::Row(row), text)
+}
+// consecutive rows make a table, the first of which is the header.
+// give every row of a table the same columns and column widths
+pub fn measure_tables(doc: &mut [(Tag, String)]) {
+    let mut start = 0;
+    while start < doc.len() {
+        let end = start + doc[start..]
+            .iter()
+            .take_while(|(t, _)| matches!(t, Tag::Row(_)))
+            .count();
+        if end == start {
+            start += 1;
+            continue
+        }
+        let mut widths: Vec<usize> = vec![];
+        for (tag, _) in doc[start..end].iter() {
+            if let Tag::Row(row) = tag {
+                for (i, cell) in row.cells.iter().enumerate() {
+                    let width = util::width(cell).max(1);
+                    match widths.get_mut(i) {
+                        Some(w) => *w = std::cmp::max(*w, width),
+                        None => widths.push(width),
+                    }
+                }
+            }
+        }
+        for (i, (tag, _)) in doc[start..end].iter_mut().enumerate() {
+            if let Tag::Row(row) = tag {
+                row.cells.resize(widths.len(), String::new());
+                row.widths = widths.clone();
+                row.header = i == 0;
+            }
+        }
+        start = end;
+    }
+}
+// gemtext, the markup of the gemini protocol. lines between
+// "```" toggles are preformatted, the toggles aren't shown
+pub fn parse_gemtext(lines: Vec<&str>) -> Vec<(Tag, String)> {
+    let mut vec = vec![];
+    let mut pre = false;
+    for line in lines.iter() {
+        if line.starts_with("```") {
+            pre = !pre;
+            continue
+        }
+        match pre {
+            true => vec.push((Tag::Pre, line.to_string())),
+            false => vec.push(parse_gemline(line)),
+        }
+    }
+    vec
+}
+pub fn parse_gemline(line: &str) -> (Tag, String) {
+    if let Some(rest) = line.strip_prefix("=>") {
+        let rest = rest.trim();
+        match rest.split_once(char::is_whitespace) {
+            Some((link, txt)) =>
+                return (Tag::Link(link.to_string()), txt.trim().to_string()),
+            None =>
+                return (Tag::Link(rest.to_string()), rest.to_string()),
+        }
+    }
+    if line.starts_with('#') {
+        return (Tag::Heading, line.trim_start_matches('#').trim().to_string())
+    }
+    if let Some(text) = line.strip_prefix("* ") {
+        return (Tag::Item(String::from("\u{2022}")), text.trim().to_string())
+    }
+    if let Some(text) = line.strip_prefix('>') {
+        return (Tag::Quote, text.trim().to_string())
     }
     (Tag::Text, line.to_string())
 }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Vec<(Tag, String)> {
+        parse_doc(vec![".t name | size", ".t a | 1", ".t longername | 12345", "after"])
+    }
+    fn row(doc: &[(Tag, String)], i: usize) -> &Row {
+        match &doc[i].0 {
+            Tag::Row(row) => row,
+            tag => panic!("not a row: {:?}", tag),
+        }
+    }
+    #[test]
+    fn columns_take_the_widest_cell() {
+        let doc = table();
+        for i in 0..3 {
+            assert_eq!(row(&doc, i).widths, [10, 5]);
+        }
+        assert!(row(&doc, 0).header);
+        assert!(!row(&doc, 1).header);
+        assert_eq!(doc[3].0, Tag::Text);
+    }
+    #[test]
+    fn rows_line_up() {
+        let doc = table();
+        let header = doc[0].0.wrap(&doc[0].1, 40);
+        assert_eq!(header, ["name       | size", "-----------+------"]);
+        assert_eq!(doc[1].0.wrap(&doc[1].1, 40), ["a          | 1"]);
+        assert_eq!(doc[2].0.wrap(&doc[2].1, 40), ["longername | 12345"]);
+    }
+    #[test]
+    fn wide_cells_are_cut() {
+        let doc = table();
+        let (tag, text) = &doc[2];
+        // the size column fits, the name column gets what's left
+        assert_eq!(row(&doc, 2).fitwidths(14), Some(vec![6, 5]));
+        assert_eq!(tag.cut(text, 14), ["long.. | 12345"]);
+        assert_eq!(tag.wrap(text, 14), ["longer | 12345", "name   |"]);
+        // neither fits, they share the width
+        assert_eq!(row(&doc, 2).fitwidths(12), Some(vec![5, 4]));
+    }
+    #[test]
+    fn narrow_screens_drop_the_columns() {
+        let doc = table();
+        let (tag, text) = &doc[2];
+        assert_eq!(row(&doc, 2).fitwidths(4), None);
+        assert_eq!(tag.cut(text, 4), [util::cut(text, 4)]);
+    }
+    #[test]
+    fn indented_wraps_under_indent() {
+        let rows = Tag::Indented.wrap("    one two three", 12);
+        assert_eq!(rows, ["    one two", "    three"]);
+    }
+    #[test]
+    fn text_wraps_to_margin() {
+        let rows = Tag::Text.wrap("    one two three", 12);
+        assert_eq!(rows, ["    one two", "three"]);
+    }
+    fn text(s: &str) -> String {
+        String::from(s)
+    }
+    #[test]
+    fn gemtext_lines() {
+        let doc = parse_gemtext(vec![
+            "# Title",
+            "### Small heading",
+            "=> gemini://example.org/  Example site",
+            "=>/about",
+            "* first",
+            "> quoted",
+            "plain text",
+        ]);
+        assert_eq!(doc, [
+            (Tag::Heading, text("Title")),
+            (Tag::Heading, text("Small heading")),
+            (Tag::Link(text("gemini://example.org/")), text("Example site")),
+            (Tag::Link(text("/about")), text("/about")),
+            (Tag::Item(text("\u{2022}")), text("first")),
+            (Tag::Quote, text("quoted")),
+            (Tag::Text, text("plain text")),
+        ]);
+    }
+    // the toggles aren't shown, what's between them is kept as it is
+    #[test]
+    fn gemtext_preformatted() {
+        let doc = parse_gemtext(vec!["```alt text", "# not a heading", "=> nor a link", "```", "# heading"]);
+        assert_eq!(doc, [
+            (Tag::Pre, text("# not a heading")),
+            (Tag::Pre, text("=> nor a link")),
+            (Tag::Heading, text("heading")),
+        ]);
+    }
+}
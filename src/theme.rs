@@ -0,0 +1,327 @@
+// pager/src/theme
+
+// Colors of each kind of line, and of the banner. A theme is chosen
+// by name, then its colors can be changed one at a time. Themes are
+// written in 24-bit color and brought down to what the terminal can
+// show, which may be no color at all.
+
+use crossterm::style::{Color, Colors};
+use std::env;
+
+// themes that can be given with --theme, the first is the default.
+// dark and light leave the terminal's background as it is
+pub const THEMES: [&str; 3] = ["dark", "light", "black"];
+// what can be colored, as config files name them
+pub const TARGETS: [&str; 12] = [
+    "heading", "text", "link", "quote", "item", "strong", "pre", "header", "banner",
+    "bannerline", "number", "selection",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    pub heading: Colors,
+    pub text: Colors,
+    pub link: Colors,
+    pub quote: Colors,
+    pub item: Colors,
+    pub strong: Colors,
+    pub pre: Colors,
+    // header rows of tables
+    pub header: Colors,
+    pub banner: Colors,
+    pub bannerline: Colors,
+    // line numbers in the gutter
+    pub number: Colors,
+    // rows of the line under the cursor, over the colors of the line
+    pub selection: Colors,
+}
+impl Theme {
+    pub fn byname(name: &str) -> Option<Self> {
+        let bg = Color::Reset;
+        let theme = match name {
+            "dark" => Self::new(bg, [
+                (225, 105, 105),
+                (180, 180, 180),
+                (105, 180, 225),
+                (140, 190, 140),
+                (235, 235, 235),
+                (210, 180, 120),
+                (110, 110, 110),
+            ], (55, 55, 65)),
+            "light" => Self::new(bg, [
+                (170, 40, 40),
+                (50, 50, 50),
+                (20, 90, 170),
+                (40, 120, 40),
+                (0, 0, 0),
+                (140, 90, 20),
+                (150, 150, 150),
+            ], (225, 225, 235)),
+            // the colors this pager started with
+            "black" => Self::byname("dark")?.fill(Color::Rgb {r: 0, g: 0, b: 0}),
+            _ => return None,
+        };
+        Some(theme)
+    }
+    // heading, text, link, quote, strong, pre and number, the rest
+    // follow. the selection only has a background
+    fn new(bg: Color, fg: [(u8, u8, u8); 7], selection: (u8, u8, u8)) -> Self {
+        let (r, g, b) = selection;
+        let [heading, text, link, quote, strong, pre, number] =
+            fg.map(|(r, g, b)| Colors::new(Color::Rgb {r, g, b}, bg));
+        Self {
+            heading,
+            text,
+            link,
+            quote,
+            item: text,
+            strong,
+            pre,
+            header: heading,
+            banner: text,
+            bannerline: text,
+            number,
+            selection: Colors {foreground: None, background: Some(Color::Rgb {r, g, b})},
+        }
+    }
+    // the same background everywhere but the selection
+    fn fill(mut self, bg: Color) -> Self {
+        for target in TARGETS.iter().filter(|t| **t != "selection") {
+            self.set(target, Colors {foreground: None, background: Some(bg)});
+        }
+        self
+    }
+    // the colors given replace the theme's, those left out stay
+    pub fn set(&mut self, target: &str, colors: Colors) -> bool {
+        let Some(c) = self.get(target) else {
+            return false
+        };
+        if colors.foreground.is_some() {
+            c.foreground = colors.foreground;
+        }
+        if colors.background.is_some() {
+            c.background = colors.background;
+        }
+        true
+    }
+    // every color as close as the terminal can show it
+    pub fn fordepth(mut self, depth: Depth) -> Self {
+        for target in TARGETS.iter() {
+            if let Some(c) = self.get(target) {
+                c.foreground = c.foreground.map(|c| depth.map(c));
+                c.background = c.background.map(|c| depth.map(c));
+            }
+        }
+        self
+    }
+    fn get(&mut self, target: &str) -> Option<&mut Colors> {
+        match target {
+            "heading" => Some(&mut self.heading),
+            "text" => Some(&mut self.text),
+            "link" => Some(&mut self.link),
+            "quote" => Some(&mut self.quote),
+            "item" => Some(&mut self.item),
+            "strong" => Some(&mut self.strong),
+            "pre" => Some(&mut self.pre),
+            "header" => Some(&mut self.header),
+            "banner" => Some(&mut self.banner),
+            "bannerline" => Some(&mut self.bannerline),
+            "number" => Some(&mut self.number),
+            "selection" => Some(&mut self.selection),
+            _ => None,
+        }
+    }
+}
+impl Default for Theme {
+    fn default() -> Self {
+        Self::byname(THEMES[0]).unwrap_or_else(|| unreachable!())
+    }
+}
+// how many colors the terminal shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Depth {
+    None,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+// the 16 colors, as xterm shows them
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+// levels of each channel in the 6x6x6 cube of 256 colors
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Depth {
+    // NO_COLOR turns color off, COLORTERM tells of 24-bit color, and
+    // TERM tells the rest
+    pub fn detect() -> Self {
+        let var = |name| env::var_os(name).map(|v| v.to_string_lossy().into_owned()).unwrap_or_default();
+        Self::fromenv(&var("NO_COLOR"), &var("COLORTERM"), &var("TERM"))
+    }
+    fn fromenv(nocolor: &str, colorterm: &str, term: &str) -> Self {
+        if !nocolor.is_empty() {
+            return Self::None
+        }
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            return Self::TrueColor
+        }
+        if term.contains("direct") {
+            Self::TrueColor
+        } else if term.contains("256") {
+            Self::Ansi256
+        } else if term.is_empty() || term == "dumb" {
+            Self::None
+        } else {
+            Self::Ansi16
+        }
+    }
+    pub fn map(&self, color: Color) -> Color {
+        let rgb = match color {
+            Color::Reset => return color,
+            Color::Rgb {r, g, b} => (r, g, b),
+            Color::AnsiValue(n) => ansirgb(n),
+            // one of the 16 colors
+            _ => match self {
+                Self::None => return Color::Reset,
+                _ => return color,
+            },
+        };
+        match self {
+            Self::None => Color::Reset,
+            Self::Ansi16 => nearest16(rgb),
+            Self::Ansi256 => Color::AnsiValue(nearest256(rgb)),
+            Self::TrueColor => Color::Rgb {r: rgb.0, g: rgb.1, b: rgb.2},
+        }
+    }
+}
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+// which channels are on, and whether bright, so hues aren't lost to
+// the grays as they are when going by distance
+fn nearest16(rgb: (u8, u8, u8)) -> Color {
+    let on = |v: u8| usize::from(v >= 128);
+    let bits = on(rgb.0) | on(rgb.1) << 1 | on(rgb.2) << 2;
+    let brightness = (u32::from(rgb.0.max(rgb.1).max(rgb.2)) * 2 + 127) / 255;
+    match brightness {
+        0 => Color::Black,
+        1 => ANSI16[bits].0,
+        _ => ANSI16[bits + 8].0,
+    }
+}
+// the closest of the color cube and the gray ramp
+fn nearest256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |v: u8| {
+        (0..CUBE.len())
+            .min_by_key(|i| (i32::from(CUBE[*i]) - i32::from(v)).abs())
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (16 + 36 * r + 6 * g + b) as u8;
+    let average = (u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23) as u8;
+    match distance(rgb, ansirgb(gray)) < distance(rgb, ansirgb(cube)) {
+        true => gray,
+        false => cube,
+    }
+}
+// what one of the 256 colors looks like
+fn ansirgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..16 => ANSI16[usize::from(n)].1,
+        16..232 => {
+            let i = usize::from(n - 16);
+            (CUBE[i / 36], CUBE[i / 6 % 6], CUBE[i % 6])
+        }
+        _ => {
+            let v = 8 + 10 * (n - 232);
+            (v, v, v)
+        }
+    }
+}
+// "225,105,105", "#e16969", a name like "red", or "default" for the
+// terminal's own color
+pub fn parsecolor(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let n = u32::from_str_radix(hex, 16).ok().filter(|_| hex.len() == 6)?;
+        let [_, r, g, b] = n.to_be_bytes();
+        return Some(Color::Rgb {r, g, b})
+    }
+    if let [r, g, b] = text.split(',').collect::<Vec<&str>>()[..] {
+        let (r, g, b) = (r.trim().parse().ok()?, g.trim().parse().ok()?, b.trim().parse().ok()?);
+        return Some(Color::Rgb {r, g, b})
+    }
+    match text {
+        "default" => Some(Color::Reset),
+        _ => Color::try_from(text).ok(),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color::Rgb {r, g, b}
+    }
+    #[test]
+    fn detect_from_environment() {
+        assert_eq!(Depth::fromenv("1", "truecolor", "xterm-256color"), Depth::None);
+        assert_eq!(Depth::fromenv("", "truecolor", "xterm"), Depth::TrueColor);
+        assert_eq!(Depth::fromenv("", "24bit", ""), Depth::TrueColor);
+        assert_eq!(Depth::fromenv("", "", "xterm-direct"), Depth::TrueColor);
+        assert_eq!(Depth::fromenv("", "", "xterm-256color"), Depth::Ansi256);
+        assert_eq!(Depth::fromenv("", "", "xterm"), Depth::Ansi16);
+        assert_eq!(Depth::fromenv("", "", "dumb"), Depth::None);
+        assert_eq!(Depth::fromenv("", "", ""), Depth::None);
+    }
+    #[test]
+    fn map_to_256() {
+        assert_eq!(Depth::Ansi256.map(rgb(255, 0, 0)), Color::AnsiValue(196));
+        assert_eq!(Depth::Ansi256.map(rgb(95, 135, 175)), Color::AnsiValue(67));
+        // grays go to the gray ramp rather than the cube
+        assert_eq!(Depth::Ansi256.map(rgb(128, 128, 128)), Color::AnsiValue(244));
+        assert_eq!(Depth::Ansi256.map(Color::Blue), Color::Blue);
+        assert_eq!(Depth::TrueColor.map(Color::AnsiValue(196)), rgb(255, 0, 0));
+    }
+    // hues are kept, brightness picks the dark or bright half
+    #[test]
+    fn map_to_16() {
+        assert_eq!(Depth::Ansi16.map(rgb(255, 0, 0)), Color::Red);
+        assert_eq!(Depth::Ansi16.map(rgb(128, 0, 0)), Color::DarkRed);
+        assert_eq!(Depth::Ansi16.map(rgb(225, 105, 105)), Color::Red);
+        assert_eq!(Depth::Ansi16.map(rgb(10, 10, 10)), Color::Black);
+        assert_eq!(Depth::Ansi16.map(Color::AnsiValue(21)), Color::Blue);
+    }
+    #[test]
+    fn no_color() {
+        assert_eq!(Depth::None.map(rgb(255, 0, 0)), Color::Reset);
+        assert_eq!(Depth::None.map(Color::Blue), Color::Reset);
+        assert_eq!(Depth::None.map(Color::AnsiValue(21)), Color::Reset);
+        for name in THEMES {
+            let mut theme = Theme::byname(name).unwrap().fordepth(Depth::None);
+            for target in TARGETS {
+                let colors = *theme.get(target).unwrap();
+                for c in [colors.foreground, colors.background] {
+                    assert!(matches!(c, None | Some(Color::Reset)), "{} {} {:?}", name, target, c);
+                }
+            }
+        }
+    }
+}
@@ -2,8 +2,13 @@
 
 use crate::util::{Rect, View};
 use crate::tabs::{TabMgr};
+use crate::config::Options;
+use crate::keys::{Bindings, Command, Key};
+use crate::format::Registry;
+use crate::backend::Backend;
+use crate::screen::Screen;
 use crossterm::event::{Event, KeyEvent, KeyEventKind, KeyCode, KeyModifiers};
-use std::io::{self, Write, Stdout};
+use std::io;
 
 #[derive(Clone, Debug)]
 pub struct UI {
@@ -12,32 +17,53 @@ pub struct UI {
     // view currently in use
     view: View,
     tabs: TabMgr,
+    // commands keys are bound to
+    bindings: Bindings,
+    // what is on the terminal
+    screen: Screen,
+    // views that aren't shown yet
+    #[allow(dead_code)]
     history: String,
+    #[allow(dead_code)]
     bookmarks: String,
 } 
 impl UI {
     // default view is View::Tab
-    pub fn new(path: &str, w: u16, h: u16) -> Self {
+    pub fn new(
+        paths: &[String],
+        formats: Registry,
+        options: Options,
+        w: u16,
+        h: u16,
+    ) -> io::Result<Self> {
         let rect = Rect::new(0, 0, w, h);
-        Self {
-            tabs: TabMgr::new(&rect, path),
-            rect: rect,
+        let bindings = options.bindings();
+        Ok(Self {
+            bindings,
+            tabs: TabMgr::new(&rect, paths, formats, options)?,
+            rect,
+            screen: Screen::new(w, h),
             view: View::Tab,
             history: String::from(""),
             bookmarks: String::from(""),
-        }
+        })
     }
     // display the current view
-    pub fn view(&self, mut stdout: &Stdout) -> io::Result<()> {
-        match &self.view {
-            View::Tab => self.tabs.view(stdout),
-            _ => Ok(()),
-        }?;
-        stdout.flush()
+    pub fn view(&mut self, backend: &mut dyn Backend) -> io::Result<()> {
+        self.screen.clear();
+        if let View::Tab = &self.view {
+            self.tabs.view(&mut self.screen);
+        }
+        self.screen.flush(backend)
+    }
+    // tell the user something in the current view
+    pub fn notify(&mut self, message: &str) {
+        self.tabs.notify(message);
     }
     // resize all views, maybe do this in parallel?
     fn resize(&mut self, w: u16, h: u16) {
         self.rect = Rect::new(0, 0, w, h);
+        self.screen.resize(w, h);
         self.tabs.resize(&self.rect);
     }
     // Resize and Control-C is handled here, 
@@ -57,13 +83,24 @@ impl UI {
                 self.view = View::Quit;
                 true
             }
-            Event::Key(KeyEvent {
-                code: keycode, 
+            // keys are typed into dialogs, otherwise they are commands
+            Event::Key(key @ KeyEvent {
                 kind: KeyEventKind::Press, 
                 ..
             }) => 
                 match &self.view {
-                    View::Tab => self.tabs.update(&keycode),
+                    View::Tab if self.tabs.indialog() => self.tabs.update(&key.code),
+                    View::Tab => match self.bindings.feed(Key::from(&key)) {
+                        Some(Command::Quit) => {
+                            self.view = View::Quit;
+                            true
+                        }
+                        Some(command) => {
+                            let count = self.bindings.takecount();
+                            self.tabs.command(command, count)
+                        }
+                        None => false,
+                    },
                     _ => false,
                 }
             _ => false,
@@ -71,9 +108,330 @@ impl UI {
     }
     // no need to derive PartialEq for View
     pub fn quit(&self) -> bool {
-        match self.view {
-            View::Quit => true,
-            _ => false,
-        }
+        matches!(self.view, View::Quit)
     }
 } 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::Grid;
+    use crate::config::{Numbers, Start};
+    use crate::theme::Depth;
+    use crossterm::style::Color;
+    use crate::util::TempDir;
+
+    // the default options, in full color whatever the terminal
+    fn options() -> Options {
+        Options {depth: Some(Depth::TrueColor), ..Options::default()}
+    }
+    // a UI on a file of numbered lines, with the pager keys
+    fn open(dir: &TempDir, lines: usize, w: u16, h: u16) -> UI {
+        opentext(dir, &numbered("line", lines), options(), w, h)
+    }
+    fn opentext(dir: &TempDir, text: &str, options: Options, w: u16, h: u16) -> UI {
+        let path = dir.file("doc", text.as_bytes());
+        UI::new(&[path], Registry::builtin(), options, w, h).unwrap()
+    }
+    fn numbered(word: &str, lines: usize) -> String {
+        (1..=lines).map(|i| format!("{} {}\n", word, i)).collect()
+    }
+    fn press(ui: &mut UI, keys: &[KeyCode]) {
+        for key in keys {
+            ui.update(Event::Key(KeyEvent::new(*key, KeyModifiers::NONE)));
+        }
+    }
+    fn ctrl(ui: &mut UI, c: char) {
+        ui.update(Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)));
+    }
+    // the rows under the tab bar that are highlighted to the edge
+    fn highlighted(grid: &Grid) -> Vec<String> {
+        let selection = options().palette().selection.background;
+        let rows = grid.rows();
+        (2..grid.h)
+            .filter(|y| grid.cell(grid.w - 1, *y).colors.background == selection)
+            .map(|y| rows[usize::from(y)].clone())
+            .collect()
+    }
+    // the rows under the tab bar
+    fn page(ui: &mut UI, grid: &mut Grid) -> Vec<String> {
+        ui.view(grid).unwrap();
+        let rows = grid.rows();
+        assert!(rows[0].starts_with("1/1: "), "{:?}", rows);
+        rows[2..].to_vec()
+    }
+    fn lines(range: std::ops::RangeInclusive<usize>) -> Vec<String> {
+        range.map(|i| format!("line {}", i)).collect()
+    }
+    #[test]
+    fn opening_shows_the_start() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        assert_eq!(page(&mut ui, &mut grid), lines(1..=6));
+        assert_eq!(grid.rows()[1], "-".repeat(20));
+        assert_eq!(grid.cursor, None);
+    }
+    #[test]
+    fn scrolling() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        page(&mut ui, &mut grid);
+        // the cursor moves to the bottom row before the page scrolls
+        press(&mut ui, &[KeyCode::Char('i'); 5]);
+        assert_eq!(page(&mut ui, &mut grid), lines(1..=6));
+        press(&mut ui, &[KeyCode::Char('i'); 3]);
+        assert_eq!(page(&mut ui, &mut grid), lines(4..=9));
+        press(&mut ui, &[KeyCode::PageDown]);
+        assert_eq!(page(&mut ui, &mut grid), lines(10..=15));
+        press(&mut ui, &[KeyCode::End]);
+        assert_eq!(page(&mut ui, &mut grid), lines(25..=30));
+        press(&mut ui, &[KeyCode::Home]);
+        assert_eq!(page(&mut ui, &mut grid), lines(1..=6));
+    }
+    #[test]
+    fn resizing_keeps_the_place() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 8]);
+        assert_eq!(page(&mut ui, &mut grid), lines(4..=9));
+        ui.update(Event::Resize(12, 5));
+        assert_eq!(page(&mut ui, &mut grid), lines(4..=6));
+        assert_eq!(grid.rows()[1], "-".repeat(12));
+        ui.update(Event::Resize(20, 10));
+        assert_eq!(page(&mut ui, &mut grid), lines(4..=11));
+    }
+    #[test]
+    fn go_to_line_dialog() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('g')]);
+        let rows = page(&mut ui, &mut grid);
+        assert_eq!(rows[2], "  go to line:");
+        assert!(grid.cursor.is_some());
+        press(&mut ui, &[KeyCode::Char('2'), KeyCode::Char('0')]);
+        assert!(page(&mut ui, &mut grid)[4].contains("20"));
+        press(&mut ui, &[KeyCode::Enter]);
+        assert_eq!(page(&mut ui, &mut grid), lines(20..=25));
+        assert_eq!(grid.cursor, None);
+    }
+    #[test]
+    fn escape_closes_the_dialog() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('g'), KeyCode::Char('9'), KeyCode::Esc]);
+        assert_eq!(page(&mut ui, &mut grid), lines(1..=6));
+        assert!(!ui.quit());
+    }
+    // tabs on the same file keep their own places, and a reload
+    // brings the new version to all of them
+    #[test]
+    fn tabs_on_one_file() {
+        let dir = TempDir::new("ui");
+        let path = dir.file("doc", numbered("line", 30).as_bytes());
+        let paths = [path.clone(), path.clone()];
+        let mut ui = UI::new(&paths, Registry::builtin(), options(), 20, 8).unwrap();
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 8]);
+        ui.view(&mut grid).unwrap();
+        assert!(grid.rows()[0].starts_with("1/2: "));
+        assert_eq!(grid.rows()[2..], lines(4..=9));
+        press(&mut ui, &[KeyCode::Char('n')]);
+        ui.view(&mut grid).unwrap();
+        assert!(grid.rows()[0].starts_with("2/2: "));
+        assert_eq!(grid.rows()[2..], lines(1..=6));
+        std::fs::write(&path, numbered("new", 30)).unwrap();
+        press(&mut ui, &[KeyCode::Char('r')]);
+        ui.view(&mut grid).unwrap();
+        assert_eq!(grid.rows()[2], "new 1");
+        press(&mut ui, &[KeyCode::Char('e')]);
+        ui.view(&mut grid).unwrap();
+        assert!(grid.rows()[0].starts_with("1/2: "));
+        assert_eq!(grid.rows()[2..4], ["new 4", "new 5"]);
+    }
+    // the view scrolls under the cursor until its line leaves the
+    // view, and stops at the ends
+    #[test]
+    fn scrolling_the_view() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 2]);
+        ctrl(&mut ui, 'y');
+        assert_eq!(page(&mut ui, &mut grid), lines(1..=6));
+        assert_eq!(highlighted(&grid), lines(3..=3));
+        ctrl(&mut ui, 'e');
+        assert_eq!(page(&mut ui, &mut grid), lines(2..=7));
+        assert_eq!(highlighted(&grid), lines(3..=3));
+        for _ in 0..30 {
+            ctrl(&mut ui, 'e');
+        }
+        assert_eq!(page(&mut ui, &mut grid), lines(25..=30));
+        assert_eq!(highlighted(&grid), lines(25..=25));
+        ctrl(&mut ui, 'y');
+        assert_eq!(page(&mut ui, &mut grid), lines(24..=29));
+        assert_eq!(highlighted(&grid), lines(25..=25));
+    }
+    #[test]
+    fn placing_the_cursor_line() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 30, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 12]);
+        assert_eq!(page(&mut ui, &mut grid), lines(8..=13));
+        press(&mut ui, &[KeyCode::Char('z')]);
+        assert_eq!(page(&mut ui, &mut grid), lines(10..=15));
+        press(&mut ui, &[KeyCode::Char('t')]);
+        assert_eq!(page(&mut ui, &mut grid), lines(13..=18));
+        press(&mut ui, &[KeyCode::Char('b')]);
+        assert_eq!(page(&mut ui, &mut grid), lines(8..=13));
+        assert_eq!(highlighted(&grid), lines(13..=13));
+        // near the ends the view goes as far as it can
+        press(&mut ui, &[KeyCode::Home, KeyCode::Char('i'), KeyCode::Char('z')]);
+        assert_eq!(page(&mut ui, &mut grid), lines(1..=6));
+        press(&mut ui, &[KeyCode::End, KeyCode::Char('t')]);
+        assert_eq!(page(&mut ui, &mut grid), lines(25..=30));
+        assert_eq!(highlighted(&grid), lines(30..=30));
+    }
+    // a document shorter than the view doesn't move
+    #[test]
+    fn centering_a_short_document() {
+        let dir = TempDir::new("ui");
+        let mut ui = open(&dir, 4, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 3]);
+        for key in ['z', 't', 'b'] {
+            press(&mut ui, &[KeyCode::Char(key)]);
+            let rows = page(&mut ui, &mut grid);
+            assert_eq!(rows[..4], lines(1..=4));
+            assert_eq!(highlighted(&grid), lines(4..=4));
+        }
+    }
+    // the cursor stays on its source line whether it's wrapped or cut
+    #[test]
+    fn toggling_wrap() {
+        let dir = TempDir::new("ui");
+        let text = format!("line 1\nline 2\n{}\n{}", "word ".repeat(10), numbered("line", 9));
+        let mut ui = opentext(&dir, &text, options(), 20, 8);
+        let mut grid = Grid::new();
+        assert_eq!(page(&mut ui, &mut grid)[2..5], [
+            "word word word word",
+            "word word word word",
+            "word word",
+        ]);
+        press(&mut ui, &[KeyCode::Char('i'); 3]);
+        press(&mut ui, &[KeyCode::Char('w')]);
+        let rows = page(&mut ui, &mut grid);
+        assert_eq!(rows[..2], lines(1..=2));
+        assert!(rows[2].starts_with("word word"), "{:?}", rows);
+        assert_eq!(rows[3], "line 1");
+        assert_eq!(highlighted(&grid), rows[2..3]);
+        press(&mut ui, &[KeyCode::Char('w')]);
+        let rows = page(&mut ui, &mut grid);
+        assert_eq!(highlighted(&grid), rows[2..5]);
+        press(&mut ui, &[KeyCode::Char('i'); 3]);
+        page(&mut ui, &mut grid);
+        assert_eq!(highlighted(&grid), ["line 1"]);
+    }
+    // only the first row of a wrapped line is numbered
+    #[test]
+    fn numbering_wrapped_lines() {
+        let dir = TempDir::new("ui");
+        let text = format!("line 1\nline 2\n{}\n{}", "word ".repeat(10), numbered("line", 9));
+        let options = Options {numbers: Numbers::Absolute, ..options()};
+        let mut ui = opentext(&dir, &text, options, 20, 8);
+        let mut grid = Grid::new();
+        assert_eq!(page(&mut ui, &mut grid), [
+            " 1 line 1",
+            " 2 line 2",
+            " 3 word word word",
+            "   word word word",
+            "   word word word",
+            "   word",
+        ]);
+    }
+    // the cursor's line has its own number, the others count from it
+    #[test]
+    fn relative_numbers() {
+        let dir = TempDir::new("ui");
+        let options = Options {numbers: Numbers::Relative, ..options()};
+        let mut ui = opentext(&dir, &numbered("line", 30), options, 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 2]);
+        assert_eq!(page(&mut ui, &mut grid), [
+            " 2 line 1",
+            " 1 line 2",
+            " 3 line 3",
+            " 1 line 4",
+            " 2 line 5",
+            " 3 line 6",
+        ]);
+    }
+    // the gutter fits the last line number, but takes no more than
+    // half of a narrow terminal
+    #[test]
+    fn gutter_width() {
+        let dir = TempDir::new("ui");
+        let options = Options {numbers: Numbers::Absolute, ..options()};
+        let mut ui = opentext(&dir, &numbered("line", 1000), options, 20, 8);
+        let mut grid = Grid::new();
+        assert_eq!(page(&mut ui, &mut grid)[..2], ["   1 line 1", "   2 line 2"]);
+        ui.update(Event::Resize(6, 8));
+        assert_eq!(page(&mut ui, &mut grid)[..3], [" 1 lin", "   e 1", " 2 lin"]);
+    }
+    // every row of the cursor's line is highlighted, the terminal
+    // cursor is hidden
+    #[test]
+    fn highlighting_wrapped_lines() {
+        let dir = TempDir::new("ui");
+        let text = format!("line 1\nline 2\n{}\n{}", "word ".repeat(10), numbered("line", 9));
+        let mut ui = opentext(&dir, &text, options(), 20, 8);
+        let mut grid = Grid::new();
+        press(&mut ui, &[KeyCode::Char('i'); 3]);
+        let rows = page(&mut ui, &mut grid);
+        assert_eq!(highlighted(&grid), rows[2..5]);
+        assert_eq!(grid.cursor, None);
+    }
+    // the terminal cursor is shown as well when asked, and instead
+    // of the highlight when there are no colors
+    #[test]
+    fn terminal_cursor() {
+        let dir = TempDir::new("ui");
+        let text = numbered("line", 30);
+        let mut grid = Grid::new();
+        let mut ui = opentext(&dir, &text, Options {cursor: true, ..options()}, 20, 8);
+        press(&mut ui, &[KeyCode::Char('i'); 2]);
+        page(&mut ui, &mut grid);
+        assert_eq!(highlighted(&grid), lines(3..=3));
+        assert_eq!(grid.cursor, Some((0, 4)));
+        let mut ui = opentext(&dir, &text, Options {depth: Some(Depth::None), ..options()}, 20, 8);
+        press(&mut ui, &[KeyCode::Char('i'); 2]);
+        page(&mut ui, &mut grid);
+        assert_eq!(highlighted(&grid), Vec::<String>::new());
+        assert!(grid.cells.iter().all(|c| c.colors.background.is_none_or(|bg| bg == Color::Reset)));
+        assert_eq!(grid.cursor, Some((0, 4)));
+    }
+    // search looks at each line of a hex dump, the hex and the text
+    // column both, and the cursor moves over it like any document
+    #[test]
+    fn searching_a_hex_dump() {
+        let dir = TempDir::new("ui");
+        let text = format!("{}needle{}", "\0".repeat(100), "\0".repeat(100));
+        let start = Start::Search(String::from("needle"));
+        let mut ui = opentext(&dir, &text, Options {start, ..options()}, 80, 8);
+        let mut grid = Grid::new();
+        let rows = page(&mut ui, &mut grid);
+        assert!(rows[0].starts_with("00000060  00 00 00 00 6e 65 65 64"), "{:?}", rows);
+        assert!(rows[0].ends_with("|....needle......|"), "{:?}", rows);
+        assert_eq!(highlighted(&grid), rows[..1]);
+        press(&mut ui, &[KeyCode::Char('i')]);
+        let rows = page(&mut ui, &mut grid);
+        assert!(highlighted(&grid)[0].starts_with("00000070"), "{:?}", rows);
+        let start = Start::Search(String::from("6e 65 65"));
+        let mut ui = opentext(&dir, &text, Options {start, ..options()}, 80, 8);
+        assert!(page(&mut ui, &mut grid)[0].starts_with("00000060"));
+    }
+}
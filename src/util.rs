@@ -1,7 +1,11 @@
 // pager/src/util
 
 // Structs, enums, functions, and constants that are generally useful
-// or fundamental to the rest of the program.
+// or fundamental to the rest of the program. Text is measured in
+// terminal columns, where wide characters take two and combining
+// marks none.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 // View currently in use
 #[derive(Clone, Debug)]
@@ -25,174 +29,323 @@ pub struct Rect {
 }
 impl Rect {
     pub fn new(x: u16, y: u16, w: u16, h: u16) -> Self {
-        Self {x: x, y: y, w: w, h: h}
+        Self {x, y, w, h}
+    }
+}
+// a line of the source, and a row of that line on the display
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub row: usize,
+}
+impl Position {
+    pub fn new(line: usize, row: usize) -> Self {
+        Self {line, row}
     }
 }
-// cursor that scrolls over data when it can't move
+// cursor that scrolls over data when it can't move. the view is
+// where scroll says, and the cursor a row within it, so either can
+// move on its own. only the rows in view are known, the owner of the
+// data does the scrolling
 #[derive(Clone, Debug)]
 pub struct ScrollingCursor {
-    pub scroll: usize,
-    pub maxscroll: usize,
+    // position of the first row in view
+    pub scroll: Position,
     pub cursor: u16,
     pub rect: Rect,
 }
 impl ScrollingCursor {
-    // sets limits given length of text and bounding box
-    pub fn new(textlength: usize, rect: &Rect) -> Self {
-        let len = match u16::try_from(textlength) {
-            Ok(t) => t, _ => u16::MAX,
-        };
-        match len < rect.h {
-            // no scrolling allowed
-            true => Self {
-                rect: Rect::new(rect.x, rect.y, rect.w, len),
-                cursor: rect.y, 
-                scroll: 0, 
-                maxscroll: 0,
-            },
-            // scrolling allowed
-            false => Self {
-                rect: rect.clone(),
-                cursor: rect.y, 
-                scroll: 0, 
-                maxscroll: textlength - usize::from(rect.h),
-            },
+    pub fn new(rect: &Rect) -> Self {
+        Self {
+            scroll: Position::default(),
+            cursor: rect.y,
+            rect: rect.clone(),
         }
     }
-    // like Self::new method but tries to preserve scroll
-    pub fn resize(&mut self, textlength: usize, rect: &Rect) {
-        let len = match u16::try_from(textlength) {
-            Ok(t) => t, _ => u16::MAX,
-        };
-        match len < rect.h {
-            // no scrolling allowed
-            true => {
-                self.rect = Rect::new(rect.x, rect.y, rect.w, len);
-                self.scroll = 0;
-                self.maxscroll = 0;
-            },
-            // scrolling allowed
-            false => {
-                self.rect = rect.clone();
-                self.scroll = std::cmp::min(self.scroll, self.maxscroll);
-                self.maxscroll = textlength - usize::from(rect.h);
-            },
-        }
-        self.cursor = (self.rect.y + self.rect.h - 1) / 2;
-    }
-    // scroll up when cursor is at highest position
-    pub fn moveup(&mut self, step: u16) -> bool {
-        let scrollstep = usize::from(step);
-        if (self.rect.y + step) <= self.cursor {
-            self.cursor -= step;
-            true
-        } else if usize::MIN + scrollstep <= self.scroll {
-            self.scroll -= scrollstep;
-            true
-        } else {
-            false
+    // index of cursor within its bounding box
+    pub fn index(&self) -> usize {
+        usize::from(self.cursor - self.rect.y)
+    }
+    // put the cursor at an index within its bounding box
+    pub fn setindex(&mut self, index: usize) {
+        let index = u16::try_from(index).unwrap_or(u16::MAX);
+        self.cursor = self.rect.y + std::cmp::min(index, self.rect.h.saturating_sub(1));
+    }
+}
+// bytes in the largest unit that keeps the number small
+pub fn humansize(bytes: u64) -> String {
+    let units = ["K", "M", "G", "T", "P"];
+    if bytes < 1024 {
+        return bytes.to_string()
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", size, units[unit])
+}
+// how a line is laid out in the terminal, plain text by default
+pub trait Layout {
+    fn wrap(&self, text: &str, w: u16) -> Vec<String> {
+        wrap(text, w)
+    }
+    fn cut(&self, text: &str, w: u16) -> Vec<String> {
+        vec![cut(text, w)]
+    }
+}
+// tabs become spaces up to the next tab stop
+pub fn expandtabs(text: &str, tabstop: usize) -> String {
+    if !text.contains('\t') {
+        return String::from(text)
+    }
+    let mut expanded = String::new();
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let n = tabstop - column % tabstop;
+                expanded.push_str(&" ".repeat(n));
+                column += n;
+            }
+            _ => {
+                expanded.push(c);
+                column += c.width().unwrap_or(0);
+            }
         }
     }
-    // scroll down when cursor is at lowest position
-    pub fn movedown(&mut self, step: u16) -> bool {
-        let scrollstep = usize::from(step);
-        if (self.cursor + step) <= (self.rect.y + self.rect.h - 1) {
-            self.cursor += step;
-            true 
-        } else if (self.scroll + scrollstep) <= self.maxscroll {
-            self.scroll += scrollstep;
-            true
-        } else {
-            false
+    expanded
+}
+// control characters are shown as ^X, or as U+FFFD past ASCII, so
+// nothing in a document is sent to the terminal as an escape
+pub fn showcontrols(text: &str) -> String {
+    if !text.chars().any(char::is_control) {
+        return String::from(text)
+    }
+    let mut shown = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\0'..='\x1f' | '\x7f' => {
+                shown.push('^');
+                shown.push(char::from(c as u8 ^ 0x40));
+            }
+            _ if c.is_control() => shown.push('\u{fffd}'),
+            _ => shown.push(c),
         }
     }
-    // returns the start and end of displayable text
-    pub fn slicebounds(&self) -> (usize, usize) {
-        let start = self.scroll;
-        let end = self.scroll + usize::from(self.rect.h);
-        (start, end)
+    shown
+}
+// columns the text takes in the terminal
+pub fn width(text: &str) -> usize {
+    text.width()
+}
+// text padded with spaces to a number of columns
+pub fn pad(text: &str, columns: usize) -> String {
+    let w = width(text);
+    match w < columns {
+        true => format!("{}{}", text, " ".repeat(columns - w)),
+        false => String::from(text),
     }
-    // index of cursor within its bounding box
-    pub fn index(&self) -> usize {
-        usize::from(self.cursor - self.rect.y)
+}
+// byte index of the first character that doesn't fit in n columns,
+// if the text is that wide
+pub fn charindex(text: &str, n: usize) -> Option<usize> {
+    let mut column = 0;
+    for (i, c) in text.char_indices() {
+        column += c.width().unwrap_or(0);
+        if column > n {
+            return Some(i)
+        }
     }
+    None
 }
 // wrap text in terminal
 pub fn wrap(line: &str, screenwidth: u16) -> Vec<String> {
-    let width = usize::from(screenwidth);
-    let length = line.len();
+    let width = std::cmp::max(usize::from(screenwidth), 1);
     let mut wrapped: Vec<String> = vec![];
-    // assume slice bounds
-    let (mut start, mut end) = (0, width);
+    let mut rest = line;
 
-    while end < length {
-        let longest = &line[start..end];
+    while let Some(mut end) = charindex(rest, width) {
+        // a wide character goes on a row of its own when it can't fit
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let longest = &rest[..end];
         // try to break line at a space
-        match longest.rsplit_once(' ') {
+        match (rest[end..].starts_with(' '), longest.rfind(' ')) {
+            // the space is just past the edge
+            (true, _) => {
+                wrapped.push(String::from(longest));
+                rest = &rest[end + 1..];
+            }
             // there is a space to break on
-            Some((a, b)) => {
-                let shortest = match a.len() {
-                    0 => b,
-                    _ => a,
-                };
-                wrapped.push(String::from(shortest));
-                start += shortest.len();
-                end = start + width;
+            (false, Some(i)) if i > 0 => {
+                wrapped.push(String::from(&rest[..i]));
+                rest = &rest[i + 1..];
             }
             // there is no space to break on
-            None => {
+            (false, _) => {
                 wrapped.push(String::from(longest));
-                start = end;
-                end += width;
+                rest = &rest[end..];
             }
         }
     }
-    // add the remaining text
-    if start < length {
-        wrapped.push(String::from(&line[start..length]));
+    // add the remaining text, an empty line is still a line
+    if !rest.is_empty() || wrapped.is_empty() {
+        wrapped.push(String::from(rest));
     }
     wrapped
 }
-// cut text in terminal, adding "..." to indicate that it 
+// cut text in terminal, adding ".." to indicate that it
 // continues beyond the screen
 pub fn cut(line: &str, screenwidth: u16) -> String {
-    let mut width = usize::from(screenwidth);
-    if line.len() < width {
+    let width = usize::from(screenwidth);
+    if charindex(line, width).is_none() {
         return String::from(line)
-    } else {
-        width -= 2;
-        let longest = &line[..width];
-        match longest.rsplit_once(' ') {
-            Some((a, b)) => {
-                let shortest = match a.len() {
-                    0 => b,
-                    _ => a,
-                };
-                return format!("{}..", shortest)
-            }
-            None => {
-                return format!("{}..", longest)
-            }
+    }
+    // too narrow to show the ".."
+    if width <= 2 {
+        let end = charindex(line, width).unwrap_or(line.len());
+        return String::from(&line[..end])
+    }
+    let end = charindex(line, width - 2).unwrap_or(line.len());
+    let longest = &line[..end];
+    match longest.rsplit_once(' ') {
+        Some((a, b)) => {
+            let shortest = match a.len() {
+                0 => b,
+                _ => a,
+            };
+            format!("{}..", shortest)
+        }
+        None => {
+            format!("{}..", longest)
         }
-
     }
 }
-// call cut for each element in the list
-pub fn cutlist<T>(lines: &Vec<(T, String)>, w: u16) -> Vec<(usize, String)> {
-    let mut display: Vec<(usize, String)> = vec![];
-    for (i, (t, l)) in lines.iter().enumerate() {
-        display.push((i, cut(l, w)));
+// the columns of text from a column on that fit in the terminal.
+// where the text carries on past an edge, a column at that edge is
+// given to a '<' or '>' marker rather than to the text
+pub fn window(line: &str, column: usize, screenwidth: u16) -> String {
+    let mut room = usize::from(screenwidth);
+    let len = width(line);
+    if column == 0 && len <= room {
+        return String::from(line)
     }
-    display
-}
-// call wrap for each element in the list
-pub fn wraplist<T>(lines: &Vec<(T, String)>, w: u16) -> Vec<(usize, String)> {
-    let mut display: Vec<(usize, String)> = vec![];
-    for (i, (t, l)) in lines.iter().enumerate() {
-        let v = wrap(l, w);
-        for s in v.iter() {
-            display.push((i, s.to_string()));
+    let left = column > 0 && len > 0 && room > 0;
+    room -= usize::from(left);
+    let right = len > column + room && room > 0;
+    room -= usize::from(right);
+    // wide characters cut by an edge leave spaces
+    let (start, end) = (column, column + room);
+    let mut text = String::new();
+    let mut at = 0;
+    for c in line.chars() {
+        let w = c.width().unwrap_or(0);
+        if at >= start && at + w <= end {
+            text.push(c);
+        } else if at < end && at + w > start {
+            let shown = std::cmp::min(at + w, end) - std::cmp::max(at, start);
+            text.push_str(&" ".repeat(shown));
         }
+        at += w;
+    }
+    let marker = |shown: bool, c: &'static str| match shown {
+        true => c,
+        false => "",
+    };
+    format!("{}{}{}", marker(left, "<"), text, marker(right, ">"))
+}
+// a directory of its own in the temp directory for a test's files,
+// removed with everything in it when dropped
+#[cfg(test)]
+pub struct TempDir {
+    pub path: std::path::PathBuf,
+}
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "pager-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)));
+        std::fs::create_dir_all(&path).unwrap();
+        Self {path}
+    }
+    // write a file in the directory, returning its path
+    pub fn file(&self, name: &str, bytes: &[u8]) -> String {
+        let path = self.path.join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+}
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an empty line still takes a row, so blank lines between
+    // paragraphs are kept
+    #[test]
+    fn blank_lines_are_kept() {
+        assert_eq!(wrap("", 20), [""]);
+        let lines = crate::tag::parse_doc(vec!["a", "", "b"]);
+        let rows: Vec<String> = lines.iter().flat_map(|(t, l)| t.wrap(l, 20)).collect();
+        assert_eq!(rows, ["a", "", "b"]);
+    }
+    #[test]
+    fn wrap_breaks_at_spaces() {
+        assert_eq!(wrap("one two three", 8), ["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+    #[test]
+    fn window_at_the_start() {
+        assert_eq!(window("short", 0, 10), "short");
+        assert_eq!(window("abcdefghij", 0, 10), "abcdefghij");
+        assert_eq!(window("abcdefghijkl", 0, 10), "abcdefghi>");
+    }
+    // markers take a column of their own, no text is hidden under them
+    #[test]
+    fn window_in_the_middle() {
+        assert_eq!(window("abcdefghijklmnop", 4, 8), "<efghij>");
+        assert_eq!(window("abcdefghijklmnop", 10, 8), "<klmnop");
+    }
+    #[test]
+    fn window_past_the_end() {
+        assert_eq!(window("abc", 5, 8), "<");
+        assert_eq!(window("", 5, 8), "");
+    }
+    #[test]
+    fn window_without_room() {
+        assert_eq!(window("abcdef", 0, 0), "");
+        assert_eq!(window("abcdef", 2, 0), "");
+        assert_eq!(window("abcdef", 2, 1), "<");
+        assert_eq!(window("abcdef", 0, 1), ">");
+    }
+    #[test]
+    fn wide_characters_take_two_columns() {
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(wrap("日本語です", 4), ["日本", "語で", "す"]);
+        assert_eq!(wrap("日本", 1), ["日", "本"]);
+        assert_eq!(cut("日本語です", 6), "日本..");
+        assert_eq!(window("日本語です", 1, 6), "< 本 >");
+        assert_eq!(pad("日本", 6), "日本  ");
+        assert_eq!(expandtabs("日\tx", 4), "日  x");
+    }
+    #[test]
+    fn controls_are_shown() {
+        assert_eq!(showcontrols("a\x1b[31mb"), "a^[[31mb");
+        assert_eq!(showcontrols("\x7f\u{9b}"), "^?\u{fffd}");
+        assert_eq!(showcontrols("plain"), "plain");
     }
-    display
 }
@@ -1,10 +1,14 @@
 // pager/src/widget
 
 use crate::tag::GetColors;
-use crate::util::{self, Rect, ScrollingCursor};
-use crossterm::{QueueableCommand, cursor, style};
+use crate::doc::Source;
+use crate::screen::Screen;
+use crate::config::{Numbers, SearchOptions};
+use crate::theme::Theme;
+use crate::util::{self, Layout, Position, Rect, ScrollingCursor};
 use crossterm::event::{KeyCode};
-use std::io::{self, Stdout};
+use crossterm::style::Color;
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub enum DialogMsg {
@@ -30,18 +34,16 @@ impl<T: Clone + std::fmt::Debug> Dialog<T> {
     {
         Self {
             rect: rect.clone(),
-            action: action,
-            input: input,
+            action,
+            input,
             prompt: String::from(prompt), 
         }
     }
-    pub fn view(&self, mut stdout: &Stdout) -> io::Result<()> {
-        stdout
-            .queue(cursor::MoveTo(self.rect.x + 2, self.rect.y + 2))?
-            .queue(style::Print(self.prompt.as_str()))?
-            .queue(cursor::MoveTo(self.rect.x + 2, self.rect.y + 4))?
-            .queue(style::Print(format!("{:?}", self.input)))?;
-        Ok(())
+    pub fn view(&self, screen: &mut Screen) {
+        screen.moveto(self.rect.x + 2, self.rect.y + 2);
+        screen.print(&self.prompt);
+        screen.moveto(self.rect.x + 2, self.rect.y + 4);
+        screen.print(&format!("{:?}", self.input));
     }
     // No wrapping yet, so resize is straightforward
     pub fn resize(&mut self, rect: &Rect) {
@@ -76,7 +78,7 @@ impl<T: Clone + std::fmt::Debug> Dialog<T> {
             // Check for meaning in choosebox
             (InputType::Choose(t), KeyCode::Char(c)) => {
                 let chars: Vec<char> = t.1.iter().map(|e| e.0).collect();
-                match chars.contains(&c) {
+                match chars.contains(c) {
                     true => {
                         t.0 = *c;
                         Some(DialogMsg::Submit)
@@ -92,45 +94,418 @@ impl<T: Clone + std::fmt::Debug> Dialog<T> {
 #[derive(Clone, Debug)]
 pub struct Selector<T> {
     rect: Rect,
-    source: Vec<(T, String)>,
+    source: Rc<dyn Source<T>>,
     wrap: bool,
+    numbers: Numbers,
+    // whether the terminal cursor is shown as well as the highlight
+    showcursor: bool,
+    // columns between tab stops
+    tabstop: usize,
+    // columns scrolled off to the left when lines aren't wrapped
+    column: usize,
+    // rows in view, each with the index of its source line
     display: Vec<(usize, String)>,
+    // whether there are rows below the view
+    below: bool,
     pub cursor: ScrollingCursor,
-} 
-impl<T: Clone + GetColors> Selector<T> {
-    pub fn new(rect: &Rect, source: Vec<(T, String)>, wrap: bool) -> Self {
-        let display = match wrap {
-            true => util::wraplist(&source, rect.w),
-            false => util::cutlist(&source, rect.w),
-        };
-        return Self {
+}
+impl<T: Clone + GetColors + Layout> Selector<T> {
+    pub fn new(
+        rect: &Rect,
+        source: Rc<dyn Source<T>>,
+        wrap: bool,
+        numbers: Numbers,
+        showcursor: bool,
+        tabstop: usize,
+    ) -> Self {
+        let mut selector = Self {
             rect: rect.clone(),
-            wrap: wrap,
-            source: source,
-            cursor: ScrollingCursor::new(display.len(), &rect),
-            display: display,
-        }
+            wrap,
+            numbers,
+            showcursor,
+            tabstop,
+            column: 0,
+            source,
+            display: vec![],
+            below: false,
+            cursor: ScrollingCursor::new(rect),
+        };
+        selector.fill();
+        selector
     }
+    // rows are rebuilt, the cursor stays on its source line
     pub fn resize(&mut self, rect: &Rect) {
+        let line = self.display.get(self.cursor.index()).map(|(i, _)| *i);
+        let index = self.cursor.index();
         self.rect = rect.clone();
-        self.display = match self.wrap {
-            true => util::wraplist(&self.source, rect.w),
-            false => util::cutlist(&self.source, rect.w),
+        self.cursor.rect = rect.clone();
+        self.show(line.unwrap_or(0), index);
+    }
+    // switch between wrapping and cutting lines. rows are rebuilt,
+    // the cursor stays on its source line
+    pub fn togglewrap(&mut self) {
+        let line = self.line();
+        let index = self.cursor.index();
+        self.wrap = !self.wrap;
+        self.show(line, index);
+    }
+    pub fn numbers(&self) -> Numbers {
+        self.numbers
+    }
+    // show or hide the gutter. rows are rebuilt to fit beside it,
+    // the cursor stays on its source line
+    pub fn setnumbers(&mut self, numbers: Numbers) {
+        let line = self.line();
+        let index = self.cursor.index();
+        self.numbers = numbers;
+        self.show(line, index);
+    }
+    // swap in a new version of the source, the cursor stays on the
+    // same line if it's still there
+    pub fn setsource(&mut self, source: Rc<dyn Source<T>>) {
+        let line = self.display.get(self.cursor.index()).map(|(i, _)| *i);
+        self.source = source;
+        self.show(line.unwrap_or(0), self.cursor.index());
+    }
+    // every row of the line under the cursor is highlighted. the
+    // terminal cursor is shown too when asked, or when the theme
+    // can't highlight
+    pub fn view(&self, screen: &mut Screen, theme: &Theme) {
+        let highlight = theme.selection.background.filter(|c| *c != Color::Reset);
+        let width = usize::from(self.textwidth());
+        let gutter = self.gutter();
+        let positions = self.positions();
+        let current = self.line();
+        let mut tag: Option<(usize, T)> = None;
+        for (j, (i, text)) in self.display.iter().enumerate() {
+            // rows of the same line share its tag
+            if tag.as_ref().is_none_or(|(k, _)| k != i) {
+                tag = Some((*i, self.source.line(*i).0));
+            }
+            let mut colors = tag.as_ref().map(|(_, t)| t.getcolors(theme));
+            screen.moveto(self.rect.x, self.rect.y + j as u16);
+            // only the first row of a line is numbered
+            if gutter > 0 && positions[j].row == 0 {
+                let number = match self.numbers {
+                    Numbers::Relative if *i != current => i.abs_diff(current),
+                    _ => i + 1,
+                };
+                screen.setcolors(theme.number);
+                screen.print(&format!("{:>1$} ", number, usize::from(gutter) - 1));
+            }
+            screen.moveto(self.rect.x + gutter, self.rect.y + j as u16);
+            match (*i == current, highlight, colors.as_mut()) {
+                (true, Some(bg), Some(c)) => {
+                    c.background = Some(bg);
+                    screen.setcolors(*c);
+                    screen.print(&util::pad(text, width));
+                }
+                (_, _, Some(c)) => {
+                    screen.setcolors(*c);
+                    screen.print(text);
+                }
+                _ => screen.print(text),
+            }
+        }
+        match self.showcursor || highlight.is_none() {
+            true => screen.setcursor(self.rect.x + gutter, self.cursor.cursor),
+            false => screen.hidecursor(),
+        }
+    }
+    pub fn selectundercursor(&self) -> Option<T> {
+        let (i, _) = self.display.get(self.cursor.index())?;
+        Some(self.source.line(*i).0)
+    }
+    // move the cursor down, scrolling when it's at the bottom
+    pub fn movedown(&mut self, step: usize) -> bool {
+        let index = self.cursor.index();
+        let last = self.display.len().saturating_sub(1);
+        if index + step <= last {
+            self.cursor.setindex(index + step);
+            return true
+        }
+        self.cursor.setindex(last);
+        if !self.below {
+            return index != last
+        }
+        let scroll = self.forward(self.cursor.scroll, index + step - last);
+        self.cursor.scroll = std::cmp::min(scroll, self.lasttop());
+        self.fill();
+        self.cursor.setindex(self.display.len().saturating_sub(1));
+        true
+    }
+    // move the cursor up, scrolling when it's at the top
+    pub fn moveup(&mut self, step: usize) -> bool {
+        let index = self.cursor.index();
+        if step <= index {
+            self.cursor.setindex(index - step);
+            return true
+        }
+        let scroll = self.back(self.cursor.scroll, step - index);
+        let moved = index != 0 || scroll != self.cursor.scroll;
+        self.cursor.scroll = scroll;
+        self.cursor.setindex(0);
+        self.fill();
+        moved
+    }
+    // scroll the view by some rows, the cursor keeping its place on
+    // screen. at the end the cursor moves instead
+    pub fn pagedown(&mut self, rows: usize) -> bool {
+        let scroll = std::cmp::min(self.forward(self.cursor.scroll, rows), self.lasttop());
+        if scroll <= self.cursor.scroll {
+            return self.movedown(rows)
+        }
+        self.cursor.scroll = scroll;
+        self.fill();
+        true
+    }
+    pub fn pageup(&mut self, rows: usize) -> bool {
+        let scroll = self.back(self.cursor.scroll, rows);
+        if scroll == self.cursor.scroll {
+            return self.moveup(rows)
+        }
+        self.cursor.scroll = scroll;
+        self.fill();
+        true
+    }
+    // cursor on the first row
+    pub fn movetop(&mut self) {
+        self.show(0, 0);
+    }
+    // cursor on the last row, with the view as far down as it goes
+    pub fn movebottom(&mut self) {
+        self.cursor.scroll = self.lasttop();
+        self.fill();
+        self.cursor.setindex(self.display.len().saturating_sub(1));
+    }
+    // scroll the view by some rows, the cursor staying on its row
+    // of the source unless that leaves the view
+    pub fn scrolldown(&mut self, rows: usize) -> bool {
+        let scroll = std::cmp::min(self.forward(self.cursor.scroll, rows), self.lasttop());
+        self.scrollto(scroll)
+    }
+    pub fn scrollup(&mut self, rows: usize) -> bool {
+        let scroll = self.back(self.cursor.scroll, rows);
+        self.scrollto(scroll)
+    }
+    // scroll so the cursor is in the middle of the view
+    pub fn center(&mut self) -> bool {
+        let half = usize::from(self.rect.h) / 2;
+        let scroll = std::cmp::min(self.back(self.selected(), half), self.lasttop());
+        self.scrollto(scroll)
+    }
+    // scroll so the cursor is on the first row of the view
+    pub fn linetotop(&mut self) -> bool {
+        let scroll = std::cmp::min(self.selected(), self.lasttop());
+        self.scrollto(scroll)
+    }
+    // scroll so the cursor is on the last row of the view
+    pub fn linetobottom(&mut self) -> bool {
+        let rows = usize::from(self.rect.h).saturating_sub(1);
+        let scroll = self.back(self.selected(), rows);
+        self.scrollto(scroll)
+    }
+    // scroll unwrapped lines sideways, as far as the widest line in
+    // view allows
+    pub fn scrollright(&mut self, columns: usize) -> bool {
+        if self.wrap {
+            return false
+        }
+        let widest = self.lines().map(|i| self.width(i)).max().unwrap_or(0);
+        // the last column is in view with the '<' marker before it
+        let w = usize::from(self.textwidth());
+        let last = match widest > w {
+            true => widest - w + 1,
+            false => 0,
+        };
+        let column = std::cmp::min(self.column.saturating_add(columns), last);
+        self.scrollsideways(std::cmp::max(column, self.column))
+    }
+    pub fn scrollleft(&mut self, columns: usize) -> bool {
+        match self.wrap {
+            true => false,
+            false => self.scrollsideways(self.column.saturating_sub(columns)),
+        }
+    }
+    // put the first row of a line at an index in view, as close as
+    // the ends of the source allow
+    pub fn show(&mut self, line: usize, index: usize) {
+        let line = std::cmp::min(line, self.source.len().saturating_sub(1));
+        let scroll = self.back(Position::new(line, 0), index);
+        self.cursor.scroll = std::cmp::min(scroll, self.lasttop());
+        self.fill();
+        let index = self.display.iter().position(|(i, _)| *i == line);
+        self.cursor.setindex(index.unwrap_or(0));
+    }
+    // source line under the cursor
+    pub fn line(&self) -> usize {
+        self.display.get(self.cursor.index()).map_or(0, |(i, _)| *i)
+    }
+    // first line from a line on that contains the pattern, going
+    // round to the top if the search wraps
+    pub fn find(&self, pattern: &str, from: usize, search: &SearchOptions) -> Option<usize> {
+        let len = self.source.len();
+        let wrapped = match search.wrapscan {
+            true => 0..std::cmp::min(from, len),
+            false => 0..0,
+        };
+        (from..len)
+            .chain(wrapped)
+            .find(|i| search.matches(&self.source.line(*i).1, pattern))
+    }
+    fn scrollsideways(&mut self, column: usize) -> bool {
+        if column == self.column {
+            return false
+        }
+        self.column = column;
+        self.fill();
+        true
+    }
+    // source lines in view
+    fn lines(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut last = None;
+        self.display.iter().map(|(i, _)| *i).filter(move |i| last.replace(*i) != Some(*i))
+    }
+    // columns of a line when it isn't cut
+    fn width(&self, line: usize) -> usize {
+        let (tag, text) = self.source.line(line);
+        let text = util::showcontrols(&util::expandtabs(&text, self.tabstop));
+        tag.cut(&text, u16::MAX).iter().map(|row| util::width(row)).max().unwrap_or(0)
+    }
+    // move the view, keeping the cursor on the row it was on
+    fn scrollto(&mut self, scroll: Position) -> bool {
+        if scroll == self.cursor.scroll {
+            return false
+        }
+        let selected = self.selected();
+        self.cursor.scroll = scroll;
+        self.fill();
+        self.select(selected);
+        true
+    }
+    // position of each row in view
+    fn positions(&self) -> Vec<Position> {
+        let mut pos = self.cursor.scroll;
+        let mut positions = Vec::with_capacity(self.display.len());
+        for (j, (i, _)) in self.display.iter().enumerate() {
+            if j > 0 {
+                pos = match *i == pos.line {
+                    true => Position::new(pos.line, pos.row + 1),
+                    false => Position::new(*i, 0),
+                };
+            }
+            positions.push(pos);
+        }
+        positions
+    }
+    // position of the row under the cursor
+    fn selected(&self) -> Position {
+        self.positions().get(self.cursor.index()).copied().unwrap_or(self.cursor.scroll)
+    }
+    // cursor on the row at a position, or on the nearest row in view
+    fn select(&mut self, pos: Position) {
+        let positions = self.positions();
+        let index = match positions.iter().position(|p| *p == pos) {
+            Some(j) => j,
+            None if pos < self.cursor.scroll => 0,
+            None => positions.len().saturating_sub(1),
+        };
+        self.cursor.setindex(index);
+    }
+    // columns for line numbers and the space after them, wide
+    // enough for the last line
+    fn gutter(&self) -> u16 {
+        if self.numbers == Numbers::Off {
+            return 0
+        }
+        let digits = self.source.len().max(1).ilog10() as u16 + 1;
+        std::cmp::min(digits + 1, self.rect.w / 2)
+    }
+    // columns left for the text
+    fn textwidth(&self) -> u16 {
+        self.rect.w - self.gutter()
+    }
+    // display rows of a source line, there is always at least one
+    fn rows(&self, line: usize) -> Vec<String> {
+        let (tag, text) = self.source.line(line);
+        let text = util::showcontrols(&util::expandtabs(&text, self.tabstop));
+        let w = self.textwidth();
+        let mut rows = match self.wrap {
+            true => tag.wrap(&text, w),
+            false => tag
+                .cut(&text, u16::MAX)
+                .iter()
+                .map(|row| util::window(row, self.column, w))
+                .collect(),
         };
-        self.cursor.resize(self.display.len(), rect);
+        if rows.is_empty() {
+            rows.push(String::new());
+        }
+        rows
+    }
+    // the position n rows after pos, or the last position
+    fn forward(&self, mut pos: Position, mut n: usize) -> Position {
+        while n > 0 {
+            let rows = self.rows(pos.line).len();
+            let left = rows - 1 - std::cmp::min(pos.row, rows - 1);
+            if n <= left {
+                pos.row += n;
+                return pos
+            }
+            if pos.line + 1 >= self.source.len() {
+                pos.row = rows - 1;
+                return pos
+            }
+            n -= left + 1;
+            pos = Position::new(pos.line + 1, 0);
+        }
+        pos
     }
-    pub fn view(&self, mut stdout: &Stdout) -> io::Result<()> {
-        let (a, b) = self.cursor.slicebounds();
-        for (j, (i, text)) in self.display[a..b].iter().enumerate() {
-            stdout
-                .queue(cursor::MoveTo(self.rect.x, self.rect.y + j as u16))?
-                .queue(style::SetColors(self.source[*i].0.getcolors()))?
-                .queue(style::Print(text.as_str()))?;
+    // the position n rows before pos, or the first position
+    fn back(&self, mut pos: Position, mut n: usize) -> Position {
+        while n > 0 {
+            if n <= pos.row {
+                pos.row -= n;
+                return pos
+            }
+            if pos.line == 0 {
+                pos.row = 0;
+                return pos
+            }
+            n -= pos.row + 1;
+            pos = Position::new(pos.line - 1, self.rows(pos.line - 1).len() - 1);
         }
-        stdout.queue(cursor::MoveTo(0, self.cursor.cursor))?;
-        Ok(())
+        pos
     }
-    pub fn selectundercursor(&self) -> &T {
-        &self.source[self.display[self.cursor.index()].0].0
+    // the furthest the view can scroll, with the last row at the bottom
+    fn lasttop(&self) -> Position {
+        let Some(line) = self.source.len().checked_sub(1) else {
+            return Position::default()
+        };
+        let end = Position::new(line, self.rows(line).len() - 1);
+        self.back(end, usize::from(self.rect.h).saturating_sub(1))
     }
-} 
+    // rebuild the rows in view, from the top down
+    fn fill(&mut self) {
+        let h = usize::from(self.rect.h);
+        let mut pos = self.cursor.scroll;
+        self.display.clear();
+        self.below = false;
+        while pos.line < self.source.len() {
+            let mut rows = self.rows(pos.line).into_iter().skip(pos.row);
+            while self.display.len() < h {
+                match rows.next() {
+                    Some(row) => self.display.push((pos.line, row)),
+                    None => break,
+                }
+            }
+            if self.display.len() == h {
+                self.below = rows.next().is_some() || pos.line + 1 < self.source.len();
+                break
+            }
+            pos = Position::new(pos.line + 1, 0);
+        }
+        let index = std::cmp::min(self.cursor.index(), self.display.len().saturating_sub(1));
+        self.cursor.setindex(index);
+    }
+}